@@ -0,0 +1,91 @@
+use serde::{Deserialize, Serialize};
+
+/// Application that owned the clipboard (or was frontmost) when a copy was detected
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SourceApp {
+    /// Bundle identifier on macOS, WM_CLASS on X11
+    pub id: Option<String>,
+    pub name: Option<String>,
+    /// Title of the application's frontmost window
+    pub window_title: Option<String>,
+}
+
+impl SourceApp {
+    /// Whether the app has the given id or name, compared case-insensitively
+    pub fn is(&self, id_or_name: &str) -> bool {
+        let matches = |value: &Option<String>| value.as_ref().is_some_and(|value| value.eq_ignore_ascii_case(id_or_name));
+        matches(&self.id) || matches(&self.name)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AppFilterMode {
+    /// Record copies from every application except the listed ones
+    #[default]
+    Deny,
+    /// Record copies only from the listed applications
+    Allow,
+}
+
+/// Decides which applications' copies are recorded
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AppFilter {
+    pub mode: AppFilterMode,
+    /// Bundle ids / WM_CLASS values or application names, compared case-insensitively
+    pub apps: Vec<String>,
+}
+
+impl AppFilter {
+    pub fn allows(&self, app: Option<&SourceApp>) -> bool {
+        let listed = app.is_some_and(|app| self.lists(app));
+        match self.mode {
+            AppFilterMode::Deny => !listed,
+            AppFilterMode::Allow => listed,
+        }
+    }
+
+    fn lists(&self, app: &SourceApp) -> bool {
+        self.apps.iter().any(|listed| app.is(listed))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn app(id: &str, name: &str) -> SourceApp {
+        SourceApp { id: Some(id.to_string()), name: Some(name.to_string()), window_title: None }
+    }
+
+    #[test]
+    fn test_default_allows_everything() {
+        let filter = AppFilter::default();
+        assert!(filter.allows(Some(&app("com.apple.Terminal", "Terminal"))));
+        assert!(filter.allows(None));
+    }
+
+    #[test]
+    fn test_deny_list() {
+        let filter = AppFilter {
+            mode: AppFilterMode::Deny,
+            apps: vec!["com.1password.1password".to_string(), "Terminal".to_string()],
+        };
+
+        assert!(!filter.allows(Some(&app("com.1Password.1password", "1Password"))));
+        // Names match as well as ids
+        assert!(!filter.allows(Some(&app("com.apple.Terminal", "terminal"))));
+        assert!(filter.allows(Some(&app("com.apple.Safari", "Safari"))));
+        assert!(filter.allows(None));
+    }
+
+    #[test]
+    fn test_allow_list() {
+        let filter = AppFilter { mode: AppFilterMode::Allow, apps: vec!["com.apple.Safari".to_string()] };
+
+        assert!(filter.allows(Some(&app("com.apple.Safari", "Safari"))));
+        assert!(!filter.allows(Some(&app("com.apple.Terminal", "Terminal"))));
+        // Unknown sources aren't on the list
+        assert!(!filter.allows(None));
+    }
+}
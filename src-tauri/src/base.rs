@@ -1,10 +1,65 @@
-use objc2_app_kit::NSPasteboard;
+use objc2::runtime::AnyObject;
+use objc2_app_kit::{NSPasteboard, NSWorkspace};
+use objc2_core_foundation::{CFArray, CFString};
+use objc2_core_graphics::{
+    kCGNullWindowID, kCGWindowLayer, kCGWindowName, kCGWindowOwnerPID, CGWindowListCopyWindowInfo, CGWindowListOption,
+};
+use objc2_foundation::{NSArray, NSData, NSDictionary, NSNumber, NSString};
+
+use crate::app_filter::SourceApp;
+use crate::db::{ClipboardEntry, ContentType};
+use crate::markers::{ClipboardMarkers, RESTORED_TYPE};
 
 pub fn get_current_clipboard_count() -> isize {
     let pasteboard = NSPasteboard::generalPasteboard();
     pasteboard.changeCount()
 }
 
+pub fn get_clipboard_markers() -> ClipboardMarkers {
+    let pasteboard = NSPasteboard::generalPasteboard();
+    let type_names: Vec<String> = match pasteboard.types() {
+        Some(types) => types.iter().map(|t| t.to_string()).collect(),
+        None => Vec::new(),
+    };
+    ClipboardMarkers::from_types(type_names.iter().map(String::as_str))
+}
+
+/// Frontmost application, which on macOS is the one that made the latest copy
+pub fn get_frontmost_app() -> Option<SourceApp> {
+    let app = NSWorkspace::sharedWorkspace().frontmostApplication()?;
+    Some(SourceApp {
+        id: app.bundleIdentifier().map(|id| id.to_string()),
+        name: app.localizedName().map(|name| name.to_string()),
+        window_title: get_window_title(app.processIdentifier()),
+    })
+}
+
+/// Title of the frontmost on-screen window owned by the process.
+/// macOS only reports titles with the Screen Recording permission, so this is often None.
+fn get_window_title(pid: i32) -> Option<String> {
+    let windows = CGWindowListCopyWindowInfo(
+        CGWindowListOption::OptionOnScreenOnly | CGWindowListOption::ExcludeDesktopElements,
+        kCGNullWindowID,
+    )?;
+
+    // CFArray and CFString are toll-free bridged to their Foundation counterparts
+    let windows = unsafe { &*(&*windows as *const CFArray).cast::<NSArray<NSDictionary<NSString, AnyObject>>>() };
+    let owner_pid_key = unsafe { &*(kCGWindowOwnerPID as *const CFString).cast::<NSString>() };
+    let layer_key = unsafe { &*(kCGWindowLayer as *const CFString).cast::<NSString>() };
+    let name_key = unsafe { &*(kCGWindowName as *const CFString).cast::<NSString>() };
+
+    // Windows are listed front to back; layer 0 skips menus, panels and other overlays
+    windows.iter().find_map(|window| {
+        let owner_pid = window.objectForKey(owner_pid_key)?.downcast::<NSNumber>().ok()?;
+        let layer = window.objectForKey(layer_key)?.downcast::<NSNumber>().ok()?;
+        if owner_pid.intValue() != pid || layer.intValue() != 0 {
+            return None;
+        }
+        let name = window.objectForKey(name_key)?.downcast::<NSString>().ok()?.to_string();
+        (!name.is_empty()).then_some(name)
+    })
+}
+
 pub fn get_clipboard_image(_count: isize) -> Option<String>{
     use base64::{Engine as _, engine::general_purpose};
 
@@ -31,4 +86,51 @@ pub fn get_clipboard_text() -> Option<String> {
     } else {
         None
     }
-}
\ No newline at end of file
+}
+
+/// Replaces the clipboard contents with the text, returning the change count of the new contents
+pub fn set_clipboard_text(text: &str) -> Result<isize, String> {
+    let pasteboard = NSPasteboard::generalPasteboard();
+    let type_string = unsafe { objc2_app_kit::NSPasteboardTypeString };
+    let change_count = pasteboard.clearContents();
+    if pasteboard.setString_forType(&NSString::from_str(text), type_string) {
+        Ok(change_count)
+    } else {
+        Err("Failed to write text to the clipboard".to_string())
+    }
+}
+
+/// Replaces the clipboard contents with a PNG given as a data URL, as returned by get_clipboard_image
+pub fn set_clipboard_image(data_url: &str) -> Result<(), String> {
+    use base64::{Engine as _, engine::general_purpose};
+
+    let encoded = data_url.strip_prefix("data:image/png;base64,").ok_or("Unsupported image format".to_string())?;
+    let bytes = general_purpose::STANDARD.decode(encoded).map_err(|e| e.to_string())?;
+
+    let pasteboard = NSPasteboard::generalPasteboard();
+    let image_type = unsafe { objc2_app_kit::NSPasteboardTypePNG };
+    pasteboard.clearContents();
+    if pasteboard.setData_forType(Some(&NSData::from_vec(bytes)), image_type) {
+        Ok(())
+    } else {
+        Err("Failed to write image to the clipboard".to_string())
+    }
+}
+
+/// Puts a stored entry back on the clipboard, marked so it isn't recorded as a new copy
+pub fn set_clipboard_entry(entry: &ClipboardEntry) -> Result<(), String> {
+    match entry.content_type {
+        ContentType::Text => {
+            set_clipboard_text(entry.text_content.as_deref().unwrap_or_default())?;
+        }
+        ContentType::Image => set_clipboard_image(entry.image_path.as_deref().unwrap_or_default())?,
+    }
+
+    // Adding a type doesn't bump the change count, so the marker belongs to the copy just written
+    let pasteboard = NSPasteboard::generalPasteboard();
+    if pasteboard.setData_forType(Some(&NSData::new()), &NSString::from_str(RESTORED_TYPE)) {
+        Ok(())
+    } else {
+        Err("Failed to mark the clipboard contents as restored".to_string())
+    }
+}
@@ -0,0 +1,387 @@
+use std::collections::HashMap;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Condvar, LazyLock, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use x11rb::connection::{Connection, RequestConnection};
+use x11rb::protocol::xfixes::{ConnectionExt as _, SelectionEventMask};
+use x11rb::protocol::xproto::{
+    Atom, AtomEnum, ConnectionExt as _, CreateWindowAux, EventMask, PropMode, PropertyNotifyEvent, Property,
+    SelectionNotifyEvent, SelectionRequestEvent, Window, WindowClass, SELECTION_NOTIFY_EVENT,
+};
+use x11rb::protocol::Event;
+use x11rb::rust_connection::RustConnection;
+use x11rb::wrapper::ConnectionExt as _;
+use x11rb::{COPY_DEPTH_FROM_PARENT, COPY_FROM_PARENT, CURRENT_TIME, NONE};
+
+use crate::app_filter::SourceApp;
+use crate::db::{ClipboardEntry, ContentType};
+use crate::markers::{ClipboardMarkers, RESTORED_TYPE};
+
+// How long the owner of the clipboard gets to hand over its contents
+const TRANSFER_TIMEOUT: Duration = Duration::from_secs(2);
+
+x11rb::atom_manager! {
+    Atoms: AtomsCookie {
+        CLIPBOARD,
+        TARGETS,
+        INCR,
+        UTF8_STRING,
+        WM_CLASS,
+        _NET_ACTIVE_WINDOW,
+        _NET_WM_PID,
+        _NET_WM_NAME,
+        IMAGE_PNG: b"image/png",
+        RESTORED: RESTORED_TYPE.as_bytes(),
+        // Property the clipboard owner writes the requested contents to
+        TRANSFER: b"CLIPBOARDWATCHER_TRANSFER",
+    }
+}
+
+/// The X11 CLIPBOARD selection, which Wayland sessions share with XWayland.
+/// A hidden window receives the contents of other apps and serves the ones this app puts
+/// on the clipboard; a thread handles its events for as long as the process runs.
+struct X11Clipboard {
+    conn: Arc<RustConnection>,
+    window: Window,
+    root: Window,
+    atoms: Atoms,
+    shared: Arc<Shared>,
+    // One transfer at a time, since they all arrive on the same window and property
+    transfer: Mutex<()>,
+}
+
+#[derive(Default)]
+struct Shared {
+    /// Bumped whenever a window takes ownership of the clipboard
+    change_count: Mutex<isize>,
+    changed: Condvar,
+    /// Contents this app owns the clipboard with, by target
+    owned: Mutex<HashMap<Atom, (Atom, Vec<u8>)>>,
+    /// Where selection and property events go while a transfer waits for them
+    pending: Mutex<Option<Sender<Event>>>,
+}
+
+static CLIPBOARD: LazyLock<Option<X11Clipboard>> = LazyLock::new(|| match X11Clipboard::connect() {
+    Ok(clipboard) => Some(clipboard),
+    Err(e) => {
+        println!("[X11] Clipboard unavailable: {}", e);
+        None
+    }
+});
+
+impl X11Clipboard {
+    fn connect() -> Result<Self, String> {
+        let (conn, screen) = x11rb::connect(None).map_err(|e| e.to_string())?;
+        let conn = Arc::new(conn);
+        let root = conn.setup().roots[screen].root;
+        let atoms = Atoms::new(conn.as_ref()).map_err(|e| e.to_string())?.reply().map_err(|e| e.to_string())?;
+
+        let window = conn.generate_id().map_err(|e| e.to_string())?;
+        let aux = CreateWindowAux::new().event_mask(EventMask::PROPERTY_CHANGE);
+        let class = WindowClass::INPUT_OUTPUT;
+        conn.create_window(COPY_DEPTH_FROM_PARENT, window, root, 0, 0, 1, 1, 0, class, COPY_FROM_PARENT, &aux)
+            .map_err(|e| e.to_string())?;
+
+        // XFixes reports every change of clipboard owner, which stands in for the pasteboard's change count
+        conn.xfixes_query_version(5, 0).map_err(|e| e.to_string())?.reply().map_err(|e| e.to_string())?;
+        let mask = SelectionEventMask::SET_SELECTION_OWNER
+            | SelectionEventMask::SELECTION_WINDOW_DESTROY
+            | SelectionEventMask::SELECTION_CLIENT_CLOSE;
+        conn.xfixes_select_selection_input(window, atoms.CLIPBOARD, mask).map_err(|e| e.to_string())?;
+        conn.flush().map_err(|e| e.to_string())?;
+
+        let shared = Arc::new(Shared::default());
+        let events = EventLoop { conn: Arc::clone(&conn), window, atoms, shared: Arc::clone(&shared) };
+        thread::spawn(move || events.run());
+        Ok(Self { conn, window, root, atoms, shared, transfer: Mutex::new(()) })
+    }
+
+    fn change_count(&self) -> isize {
+        *self.shared.change_count.lock().unwrap()
+    }
+
+    /// Clipboard contents in the given target, or None if the owner doesn't offer it
+    fn read(&self, target: Atom) -> Result<Option<Vec<u8>>, String> {
+        let _transfer = self.transfer.lock().unwrap();
+        let (sender, receiver) = mpsc::channel();
+        *self.shared.pending.lock().unwrap() = Some(sender);
+        let result = self.transfer(target, &receiver);
+        *self.shared.pending.lock().unwrap() = None;
+        result
+    }
+
+    fn transfer(&self, target: Atom, receiver: &Receiver<Event>) -> Result<Option<Vec<u8>>, String> {
+        let conn = self.conn.as_ref();
+        conn.convert_selection(self.window, self.atoms.CLIPBOARD, target, self.atoms.TRANSFER, CURRENT_TIME)
+            .map_err(|e| e.to_string())?;
+        conn.flush().map_err(|e| e.to_string())?;
+
+        loop {
+            match receiver.recv_timeout(TRANSFER_TIMEOUT).map_err(|_| "Clipboard owner didn't respond".to_string())? {
+                Event::SelectionNotify(event) if event.property == NONE => return Ok(None),
+                Event::SelectionNotify(_) => break,
+                _ => {}
+            }
+        }
+
+        let reply = self.take_property()?;
+        if reply.0 != self.atoms.INCR {
+            return Ok(Some(reply.1));
+        }
+        // Large contents come in chunks, each written once the previous one was deleted
+        let mut data = Vec::new();
+        loop {
+            match receiver.recv_timeout(TRANSFER_TIMEOUT).map_err(|_| "Clipboard transfer stalled".to_string())? {
+                Event::PropertyNotify(event) if event.state == Property::NEW_VALUE => {
+                    let (_, chunk) = self.take_property()?;
+                    if chunk.is_empty() {
+                        return Ok(Some(data));
+                    }
+                    data.extend_from_slice(&chunk);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    // Reads and deletes the transfer property, returning its type and value
+    fn take_property(&self) -> Result<(Atom, Vec<u8>), String> {
+        let reply = self
+            .conn
+            .get_property(true, self.window, self.atoms.TRANSFER, AtomEnum::ANY, 0, u32::MAX / 4)
+            .map_err(|e| e.to_string())?
+            .reply()
+            .map_err(|e| e.to_string())?;
+        self.conn.flush().map_err(|e| e.to_string())?;
+        Ok((reply.type_, reply.value))
+    }
+
+    fn atom_name(&self, atom: Atom) -> Option<String> {
+        let reply = self.conn.get_atom_name(atom).ok()?.reply().ok()?;
+        String::from_utf8(reply.name).ok()
+    }
+
+    /// Takes ownership of the clipboard with the given contents, returning the change count of the new copy
+    fn write(&self, contents: HashMap<Atom, (Atom, Vec<u8>)>) -> Result<isize, String> {
+        let before = self.change_count();
+        *self.shared.owned.lock().unwrap() = contents;
+        self.conn.set_selection_owner(self.window, self.atoms.CLIPBOARD, CURRENT_TIME).map_err(|e| e.to_string())?;
+        let owner = self
+            .conn
+            .get_selection_owner(self.atoms.CLIPBOARD)
+            .map_err(|e| e.to_string())?
+            .reply()
+            .map_err(|e| e.to_string())?
+            .owner;
+        if owner != self.window {
+            return Err("Failed to take ownership of the clipboard".to_string());
+        }
+
+        // The change arrives as an event like any other copy
+        let count = self.shared.change_count.lock().unwrap();
+        let unchanged = |count: &mut isize| *count == before;
+        let (count, _) = self.shared.changed.wait_timeout_while(count, TRANSFER_TIMEOUT, unchanged).unwrap();
+        Ok(*count)
+    }
+
+    fn property(&self, window: Window, property: Atom, type_: impl Into<Atom>) -> Option<Vec<u8>> {
+        let reply = self.conn.get_property(false, window, property, type_, 0, u32::MAX / 4).ok()?.reply().ok()?;
+        (reply.type_ != NONE).then_some(reply.value)
+    }
+}
+
+struct EventLoop {
+    conn: Arc<RustConnection>,
+    window: Window,
+    atoms: Atoms,
+    shared: Arc<Shared>,
+}
+
+impl EventLoop {
+    fn run(self) {
+        loop {
+            let event = match self.conn.wait_for_event() {
+                Ok(event) => event,
+                Err(e) => {
+                    println!("[X11] Connection closed: {}", e);
+                    return;
+                }
+            };
+            match event {
+                Event::XfixesSelectionNotify(_) => {
+                    *self.shared.change_count.lock().unwrap() += 1;
+                    self.shared.changed.notify_all();
+                }
+                Event::SelectionRequest(request) => {
+                    if let Err(e) = self.serve(&request) {
+                        println!("[X11] Failed to hand over the clipboard: {}", e);
+                    }
+                }
+                Event::SelectionClear(_) => self.shared.owned.lock().unwrap().clear(),
+                Event::SelectionNotify(SelectionNotifyEvent { requestor, .. })
+                | Event::PropertyNotify(PropertyNotifyEvent { window: requestor, .. })
+                    if requestor == self.window =>
+                {
+                    if let Some(sender) = self.shared.pending.lock().unwrap().as_ref() {
+                        let _ = sender.send(event);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    // Hands the contents this app put on the clipboard to the app pasting them
+    fn serve(&self, request: &SelectionRequestEvent) -> Result<(), String> {
+        let owned = self.shared.owned.lock().unwrap();
+        let max_bytes = self.conn.maximum_request_bytes().saturating_sub(64);
+        let property = if request.selection != self.atoms.CLIPBOARD || owned.is_empty() {
+            NONE
+        } else if request.target == self.atoms.TARGETS {
+            let mut targets: Vec<Atom> = owned.keys().copied().collect();
+            targets.push(self.atoms.TARGETS);
+            self.conn
+                .change_property32(PropMode::REPLACE, request.requestor, request.property, AtomEnum::ATOM, &targets)
+                .map_err(|e| e.to_string())?;
+            request.property
+        } else if let Some((type_, data)) = owned.get(&request.target).filter(|(_, data)| data.len() <= max_bytes) {
+            self.conn
+                .change_property8(PropMode::REPLACE, request.requestor, request.property, *type_, data)
+                .map_err(|e| e.to_string())?;
+            request.property
+        } else {
+            NONE
+        };
+
+        let notify = SelectionNotifyEvent {
+            response_type: SELECTION_NOTIFY_EVENT,
+            sequence: 0,
+            time: request.time,
+            requestor: request.requestor,
+            selection: request.selection,
+            target: request.target,
+            property,
+        };
+        self.conn.send_event(false, request.requestor, EventMask::NO_EVENT, notify).map_err(|e| e.to_string())?;
+        self.conn.flush().map_err(|e| e.to_string())
+    }
+}
+
+pub fn get_current_clipboard_count() -> isize {
+    CLIPBOARD.as_ref().map_or(0, X11Clipboard::change_count)
+}
+
+pub fn get_clipboard_markers() -> ClipboardMarkers {
+    let Some(clipboard) = CLIPBOARD.as_ref() else {
+        return ClipboardMarkers::default();
+    };
+    let targets = match clipboard.read(clipboard.atoms.TARGETS) {
+        Ok(Some(targets)) => targets,
+        Ok(None) => return ClipboardMarkers::default(),
+        Err(e) => {
+            println!("[X11] Failed to read clipboard targets: {}", e);
+            return ClipboardMarkers::default();
+        }
+    };
+    let type_names: Vec<String> = targets
+        .chunks_exact(4)
+        .filter_map(|atom| clipboard.atom_name(u32::from_ne_bytes([atom[0], atom[1], atom[2], atom[3]])))
+        .collect();
+    ClipboardMarkers::from_types(type_names.iter().map(String::as_str))
+}
+
+/// Application of the active window, which on X11 is the one that made the latest copy in all but rare cases
+pub fn get_frontmost_app() -> Option<SourceApp> {
+    let clipboard = CLIPBOARD.as_ref()?;
+    let active = clipboard.property(clipboard.root, clipboard.atoms._NET_ACTIVE_WINDOW, AtomEnum::WINDOW)?;
+    let window = u32::from_ne_bytes(active.get(..4)?.try_into().ok()?);
+    if window == NONE {
+        return None;
+    }
+
+    // WM_CLASS holds the instance and the class name, each followed by a NUL
+    let class = clipboard.property(window, clipboard.atoms.WM_CLASS, AtomEnum::STRING);
+    let class = class.as_deref().and_then(|class| class.split(|&b| b == 0).nth(1));
+    let class = class.map(|class| String::from_utf8_lossy(class).into_owned());
+    // The process name, as WM_CLASS is often a lowercase or reverse-DNS variant of it
+    let pid = clipboard.property(window, clipboard.atoms._NET_WM_PID, AtomEnum::CARDINAL);
+    let pid = pid.and_then(|pid| Some(u32::from_ne_bytes(pid.get(..4)?.try_into().ok()?)));
+    let name = pid.and_then(|pid| std::fs::read_to_string(format!("/proc/{}/comm", pid)).ok());
+    let name = name.map(|comm| comm.trim_end().to_string());
+    if class.is_none() && name.is_none() {
+        return None;
+    }
+    Some(SourceApp { id: class, name, window_title: get_window_title(clipboard, window) })
+}
+
+fn get_window_title(clipboard: &X11Clipboard, window: Window) -> Option<String> {
+    let title = clipboard
+        .property(window, clipboard.atoms._NET_WM_NAME, clipboard.atoms.UTF8_STRING)
+        .or_else(|| clipboard.property(window, AtomEnum::WM_NAME.into(), AtomEnum::STRING))?;
+    let title = String::from_utf8_lossy(&title).into_owned();
+    (!title.is_empty()).then_some(title)
+}
+
+pub fn get_clipboard_image(_count: isize) -> Option<String> {
+    use base64::{Engine as _, engine::general_purpose};
+
+    let clipboard = CLIPBOARD.as_ref()?;
+    let data = clipboard.read(clipboard.atoms.IMAGE_PNG).unwrap_or_else(|e| {
+        println!("[X11] Failed to read clipboard image: {}", e);
+        None
+    })?;
+    Some(format!("data:image/png;base64,{}", general_purpose::STANDARD.encode(&data)))
+}
+
+pub fn get_clipboard_text() -> Option<String> {
+    let clipboard = CLIPBOARD.as_ref()?;
+    let data = clipboard.read(clipboard.atoms.UTF8_STRING).unwrap_or_else(|e| {
+        println!("[X11] Failed to read clipboard text: {}", e);
+        None
+    })?;
+    Some(String::from_utf8_lossy(&data).into_owned())
+}
+
+fn text_contents(clipboard: &X11Clipboard, text: &str) -> HashMap<Atom, (Atom, Vec<u8>)> {
+    let utf8 = clipboard.atoms.UTF8_STRING;
+    HashMap::from([(utf8, (utf8, text.as_bytes().to_vec()))])
+}
+
+fn image_contents(clipboard: &X11Clipboard, data_url: &str) -> Result<HashMap<Atom, (Atom, Vec<u8>)>, String> {
+    use base64::{Engine as _, engine::general_purpose};
+
+    let encoded = data_url.strip_prefix("data:image/png;base64,").ok_or("Unsupported image format".to_string())?;
+    let bytes = general_purpose::STANDARD.decode(encoded).map_err(|e| e.to_string())?;
+    let png = clipboard.atoms.IMAGE_PNG;
+    Ok(HashMap::from([(png, (png, bytes))]))
+}
+
+fn connected() -> Result<&'static X11Clipboard, String> {
+    CLIPBOARD.as_ref().ok_or("No X11 display to reach the clipboard".to_string())
+}
+
+/// Replaces the clipboard contents with the text, returning the change count of the new contents
+pub fn set_clipboard_text(text: &str) -> Result<isize, String> {
+    let clipboard = connected()?;
+    clipboard.write(text_contents(clipboard, text))
+}
+
+/// Replaces the clipboard contents with a PNG given as a data URL, as returned by get_clipboard_image
+pub fn set_clipboard_image(data_url: &str) -> Result<(), String> {
+    let clipboard = connected()?;
+    clipboard.write(image_contents(clipboard, data_url)?).map(|_| ())
+}
+
+/// Puts a stored entry back on the clipboard, marked so it isn't recorded as a new copy
+pub fn set_clipboard_entry(entry: &ClipboardEntry) -> Result<(), String> {
+    let clipboard = connected()?;
+    let mut contents = match entry.content_type {
+        ContentType::Text => text_contents(clipboard, entry.text_content.as_deref().unwrap_or_default()),
+        ContentType::Image => image_contents(clipboard, entry.image_path.as_deref().unwrap_or_default())?,
+    };
+    // Offered as a target of its own, like the type added to the pasteboard on macOS
+    contents.insert(clipboard.atoms.RESTORED, (clipboard.atoms.RESTORED, Vec::new()));
+    clipboard.write(contents).map(|_| ())
+}
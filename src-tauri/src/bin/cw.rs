@@ -0,0 +1,7 @@
+// Command-line access to the clipboard history, usable while the app is running
+fn main() {
+    if let Err(e) = clipboardwatcher_lib::cli::main(std::env::args().skip(1)) {
+        eprintln!("cw: {}", e);
+        std::process::exit(1);
+    }
+}
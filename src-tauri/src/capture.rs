@@ -0,0 +1,497 @@
+use std::fs::{File, OpenOptions, TryLockError};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, RwLock};
+use std::thread;
+use std::time::Duration;
+
+use crate::db::ClipboardEntry;
+use crate::db_actor::DbHandle;
+use crate::ipc::{self, Request};
+use crate::patterns::Patterns;
+use crate::pause::PauseStatus;
+use crate::pause::PauseState;
+use crate::pipeline::{ClipboardBackend, ClipboardCopy, Pipeline, SystemClipboard};
+use crate::secrets::SecretDetector;
+use crate::settings::Settings;
+
+// How often expired entries are deleted and the history limits re-checked
+const EXPIRY_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Change to the history or the capture state, delivered to every subscriber
+#[derive(Debug, Clone)]
+pub enum CaptureEvent {
+    EntryAdded(ClipboardEntry),
+    EntryDeleted(i64),
+    EntryPinned { id: i64, pinned: bool },
+    PauseChanged(PauseStatus),
+    SettingsChanged(Settings),
+    /// This process took the capture lock, either at start or after the previous holder exited
+    CaptureStarted,
+}
+
+type Subscriber = Box<dyn Fn(&CaptureEvent) + Send + Sync>;
+
+/// Lock file held by the one process that captures the clipboard into a database,
+/// so the app and a headless daemon never record the same copy twice.
+/// The operating system releases it when the process exits.
+pub struct CaptureLock {
+    _file: File,
+}
+
+impl CaptureLock {
+    /// Takes the lock for the database, or returns None if another process holds it
+    pub fn try_acquire(db_path: &Path) -> Result<Option<Self>, String> {
+        let file = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(db_path.with_extension("lock"))
+            .map_err(|e| e.to_string())?;
+        match file.try_lock() {
+            Ok(()) => Ok(Some(Self { _file: file })),
+            Err(TryLockError::WouldBlock) => Ok(None),
+            Err(TryLockError::Error(e)) => Err(e.to_string()),
+        }
+    }
+}
+
+/// Clipboard capture and storage without any user interface.
+/// The app drives it alongside its windows and tray; `cw daemon` runs it on its own.
+pub struct Capture {
+    pub db: DbHandle,
+    pub secrets: Mutex<SecretDetector>,
+    /// Compiled regexes of the current settings
+    pub patterns: RwLock<Patterns>,
+    pub settings: RwLock<Settings>,
+    pub settings_path: PathBuf,
+    pub pause: PauseState,
+    /// Held for the whole of a settings update, see begin_settings_update
+    settings_update: Mutex<()>,
+    /// Why settings must not be saved, when the file on disk couldn't be loaded or moved aside
+    settings_refusal: Option<String>,
+    db_path: PathBuf,
+    lock: Mutex<Option<CaptureLock>>,
+    /// Set while another process captures and this one follows it
+    following: AtomicBool,
+    subscribers: RwLock<Vec<Subscriber>>,
+    backend: Arc<dyn ClipboardBackend>,
+    /// Content hash of the last stored entry, which Dedupe compares new ones against
+    pub last_hash: Mutex<Option<u64>>,
+}
+
+impl Capture {
+    /// Loads the settings and opens the database, unlocking it with a stored key file if there is one
+    pub fn open(db_path: PathBuf, settings_path: PathBuf) -> Result<Self, String> {
+        Self::open_with_backend(db_path, settings_path, Arc::new(SystemClipboard))
+    }
+
+    /// Like open, capturing from the given clipboard instead of the system one
+    pub fn open_with_backend(
+        db_path: PathBuf,
+        settings_path: PathBuf,
+        backend: Arc<dyn ClipboardBackend>,
+    ) -> Result<Self, String> {
+        println!("Settings path: {:?}", settings_path);
+        let (settings, settings_refusal) = Settings::load_or_default(&settings_path);
+        let secrets = settings.secret_detector().unwrap_or_else(|_| SecretDetector::new());
+        let patterns = settings.patterns().unwrap_or_default();
+
+        println!("Database path: {:?}", db_path);
+        if let Some(parent) = db_path.parent() {
+            std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+        }
+        let db = DbHandle::open(db_path.clone())?;
+        if let Err(e) = db.read(|db| db.unlock_with_stored_key_file()) {
+            println!("Failed to unlock database with key file: {}", e);
+        }
+
+        Ok(Self {
+            db,
+            secrets: Mutex::new(secrets),
+            patterns: RwLock::new(patterns),
+            settings: RwLock::new(settings),
+            settings_path,
+            pause: PauseState::new(),
+            settings_update: Mutex::new(()),
+            settings_refusal,
+            db_path,
+            lock: Mutex::new(None),
+            following: AtomicBool::new(false),
+            subscribers: RwLock::new(Vec::new()),
+            backend,
+            last_hash: Mutex::new(None),
+        })
+    }
+
+    pub fn subscribe(&self, subscriber: impl Fn(&CaptureEvent) + Send + Sync + 'static) {
+        self.subscribers.write().unwrap().push(Box::new(subscriber));
+    }
+
+    pub fn emit(&self, event: CaptureEvent) {
+        for subscriber in self.subscribers.read().unwrap().iter() {
+            subscriber(&event);
+        }
+    }
+
+    /// Adds text to the history through the same filters as copied text
+    pub fn add_text(&self, text: String) -> Result<ClipboardEntry, String> {
+        Pipeline::added().run(ClipboardCopy::added(text), self).map_err(|e| format!("Text was not added ({})", e))
+    }
+
+    /// Starts capturing unless another process already is. In that case the history
+    /// is followed instead and capture takes over once the other process exits.
+    /// Returns whether this process is capturing.
+    pub fn start(self: &Arc<Self>) -> Result<bool, String> {
+        if self.try_take_over()? {
+            return Ok(true);
+        }
+        println!("[CAPTURE] Another process is capturing, following its entries");
+        self.following.store(true, Ordering::SeqCst);
+        let capture = Arc::clone(self);
+        thread::spawn(move || capture.follow_database());
+        Ok(false)
+    }
+
+    /// Starts capturing, failing if another process already is
+    pub fn start_exclusive(self: &Arc<Self>) -> Result<(), String> {
+        if self.try_take_over()? {
+            Ok(())
+        } else {
+            Err(format!("Another process is already capturing into {:?}", self.db_path))
+        }
+    }
+
+    pub fn db_path(&self) -> &Path {
+        &self.db_path
+    }
+
+    pub fn is_capturing(&self) -> bool {
+        self.lock.lock().unwrap().is_some()
+    }
+
+    fn try_take_over(self: &Arc<Self>) -> Result<bool, String> {
+        let Some(lock) = CaptureLock::try_acquire(&self.db_path)? else {
+            return Ok(false);
+        };
+        *self.lock.lock().unwrap() = Some(lock);
+        self.following.store(false, Ordering::SeqCst);
+        self.spawn_polling_thread();
+        self.spawn_expiry_thread();
+        self.emit(CaptureEvent::CaptureStarted);
+        Ok(true)
+    }
+
+    // Reports the capturing process's events until this one can capture itself
+    fn follow_database(self: Arc<Self>) {
+        let mut last_id = self.db.read(|db| db.latest_id()).unwrap_or(0);
+        loop {
+            if let Err(e) = self.follow_events(&mut last_id) {
+                println!("[CAPTURE] Error following the capturing process: {}", e);
+            }
+            thread::sleep(self.poll_interval());
+
+            match self.try_take_over() {
+                Ok(true) => {
+                    println!("[CAPTURE] Other process exited, capturing from now on");
+                    return;
+                }
+                Ok(false) => {}
+                Err(e) => println!("[CAPTURE] Error checking capture lock: {}", e),
+            }
+        }
+    }
+
+    // Relays events from the capturing process's control socket until it closes
+    fn follow_events(&self, last_id: &mut i64) -> Result<(), String> {
+        // The socket opens shortly after the other process takes the capture lock
+        let Some(events) = ipc::subscribe(&self.db_path)? else {
+            return Ok(());
+        };
+
+        // Catch up on entries saved while no connection was open
+        for entry in self.db.read(|db| db.get_entries_after(*last_id))? {
+            *last_id = entry.id.unwrap_or(*last_id);
+            self.emit(CaptureEvent::EntryAdded(entry));
+        }
+        self.forward(Request::Status)?;
+        self.emit(CaptureEvent::PauseChanged(self.pause.status(chrono::Utc::now())));
+
+        for event in events {
+            match &event {
+                // Already reported while catching up
+                CaptureEvent::EntryAdded(entry) if entry.id.is_some_and(|id| id <= *last_id) => continue,
+                CaptureEvent::EntryAdded(entry) => *last_id = entry.id.unwrap_or(*last_id),
+                CaptureEvent::PauseChanged(status) => self.pause.mirror(status),
+                _ => {}
+            }
+            self.emit(event);
+        }
+        Ok(())
+    }
+
+    pub fn poll_interval(&self) -> Duration {
+        self.settings.read().unwrap().poll_interval()
+    }
+
+    /// Pauses capture in whichever process is capturing
+    pub fn pause(&self) -> Result<(), String> {
+        if self.following.load(Ordering::SeqCst) {
+            return self.forward(Request::Pause { minutes: None });
+        }
+        self.pause.pause();
+        self.emit(CaptureEvent::PauseChanged(self.pause.status(chrono::Utc::now())));
+        Ok(())
+    }
+
+    pub fn pause_for(&self, duration: Duration) -> Result<(), String> {
+        if self.following.load(Ordering::SeqCst) {
+            return self.forward(Request::Pause { minutes: Some(duration.as_secs().div_ceil(60)) });
+        }
+        let now = chrono::Utc::now();
+        self.pause.pause_for(duration, now);
+        self.emit(CaptureEvent::PauseChanged(self.pause.status(now)));
+        Ok(())
+    }
+
+    pub fn resume(&self) -> Result<(), String> {
+        if self.following.load(Ordering::SeqCst) {
+            return self.forward(Request::Resume);
+        }
+        self.pause.resume();
+        self.emit(CaptureEvent::PauseChanged(self.pause.status(chrono::Utc::now())));
+        Ok(())
+    }
+
+    // Sends a pause request to the capturing process and takes over the status it answers with.
+    // Its PauseChanged event reaches the subscribers through follow_events.
+    fn forward(&self, request: Request) -> Result<(), String> {
+        let mut stream = ipc::connect(&self.db_path)?.ok_or("The capturing process isn't listening".to_string())?;
+        let status = serde_json::from_value(ipc::send(&mut stream, &request)?).map_err(|e| e.to_string())?;
+        self.pause.mirror(&status);
+        Ok(())
+    }
+
+    /// Starts a settings update. Hold the guard from reading the current settings
+    /// until apply_settings returns, so concurrent updates apply one at a time
+    /// instead of overwriting each other.
+    pub fn begin_settings_update(&self) -> Result<MutexGuard<'_, ()>, String> {
+        self.settings_update.lock().map_err(|e| e.to_string())
+    }
+
+    /// Validates, persists and applies new settings, within an update started by begin_settings_update
+    pub fn apply_settings(&self, _update: &MutexGuard<'_, ()>, settings: Settings) -> Result<(), String> {
+        if let Some(refusal) = &self.settings_refusal {
+            return Err(refusal.clone());
+        }
+        settings.validate()?;
+        let detector = settings.secret_detector()?;
+        let patterns = settings.patterns()?;
+
+        settings.save(&self.settings_path)?;
+        *self.secrets.lock().map_err(|e| e.to_string())? = detector;
+        *self.patterns.write().map_err(|e| e.to_string())? = patterns;
+        println!("Settings updated: {:?}", settings);
+        *self.settings.write().map_err(|e| e.to_string())? = settings.clone();
+        self.emit(CaptureEvent::SettingsChanged(settings));
+
+        // Tighter history limits take effect immediately
+        self.prune_history()
+    }
+
+    /// Deletes entries beyond the configured history limits
+    pub fn prune_history(&self) -> Result<(), String> {
+        let (max_entries, max_age_days) = {
+            let settings = self.settings.read().map_err(|e| e.to_string())?;
+            (settings.max_entries, settings.max_age_days)
+        };
+        if max_entries.is_none() && max_age_days.is_none() {
+            return Ok(());
+        }
+
+        // Same format as ClipboardEntry::created_at, so the strings compare chronologically
+        let created_before = max_age_days
+            .map(|days| (chrono::Utc::now() - chrono::Duration::days(days as i64)).to_string());
+        let ids = self.db.write(move |db| db.prune_history(max_entries, created_before.as_deref()))?;
+        for id in ids {
+            println!("Pruned entry with id: {}", id);
+            self.emit(CaptureEvent::EntryDeleted(id));
+        }
+        Ok(())
+    }
+
+    fn spawn_polling_thread(self: &Arc<Self>) {
+        let capture = Arc::clone(self);
+        let backend = Arc::clone(&self.backend);
+        let mut pipeline = Pipeline::standard(Arc::clone(&backend));
+        let mut current_count = 0;
+        println!("[POLLING] Spawning clipboard polling thread");
+        thread::spawn(move || loop {
+            if capture.pause.expire(chrono::Utc::now()) {
+                println!("[POLLING] Pause timer elapsed, resuming capture");
+                capture.emit(CaptureEvent::PauseChanged(capture.pause.status(chrono::Utc::now())));
+            }
+
+            let new_count = backend.change_count();
+            if new_count != current_count {
+                println!("[POLLING] Clipboard count changed: {} -> {}", current_count, new_count);
+                current_count = new_count;
+
+                // The count is still tracked while paused, so nothing copied meanwhile is picked up on resume
+                if capture.pause.is_paused(chrono::Utc::now()) {
+                    println!("[POLLING] Capture paused, ignoring change");
+                } else {
+                    let _ = pipeline.run(ClipboardCopy::capture(backend.as_ref(), new_count), &capture);
+                }
+            }
+
+            thread::sleep(capture.poll_interval());
+        });
+    }
+
+    fn spawn_expiry_thread(self: &Arc<Self>) {
+        let capture = Arc::clone(self);
+        println!("[EXPIRY] Spawning entry expiry thread");
+        thread::spawn(move || loop {
+            thread::sleep(EXPIRY_INTERVAL);
+
+            let now = chrono::Utc::now().timestamp();
+            match capture.db.write(move |db| db.delete_expired_entries(now)) {
+                Ok(ids) => {
+                    for id in ids {
+                        println!("[EXPIRY] Deleted expired entry with id: {}", id);
+                        capture.emit(CaptureEvent::EntryDeleted(id));
+                    }
+                }
+                Err(e) => println!("[EXPIRY] Error deleting expired entries: {}", e),
+            }
+
+            // Catches entries aging past max_age_days while nothing is copied
+            if let Err(e) = capture.prune_history() {
+                println!("[EXPIRY] Error pruning history: {}", e);
+            }
+        });
+    }
+}
+
+/// Capture on a database of its own in the temp dir, which is removed when the test is done
+#[cfg(test)]
+pub struct TestCapture {
+    capture: Arc<Capture>,
+    dir: PathBuf,
+}
+
+#[cfg(test)]
+impl TestCapture {
+    /// The name must be unique across tests, which run at the same time
+    pub fn new(name: &str) -> Self {
+        let dir = std::env::temp_dir().join(format!("clipboard_{}_{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let capture = Self::open(&dir);
+        Self { capture, dir }
+    }
+
+    /// Another Capture on the same database and settings, as a second process would open them
+    pub fn open_again(&self) -> Arc<Capture> {
+        Self::open(&self.dir)
+    }
+
+    fn open(dir: &Path) -> Arc<Capture> {
+        let backend = Arc::new(IdleClipboard);
+        Arc::new(Capture::open_with_backend(dir.join("history.db"), dir.join("settings.json"), backend).unwrap())
+    }
+}
+
+/// Clipboard nothing is ever copied to, so tests that start capturing leave the system one alone
+#[cfg(test)]
+struct IdleClipboard;
+
+#[cfg(test)]
+impl ClipboardBackend for IdleClipboard {
+    fn change_count(&self) -> isize {
+        0
+    }
+
+    fn frontmost_app(&self) -> Option<crate::app_filter::SourceApp> {
+        None
+    }
+
+    fn markers(&self) -> crate::markers::ClipboardMarkers {
+        crate::markers::ClipboardMarkers::default()
+    }
+
+    fn text(&self) -> Option<String> {
+        None
+    }
+
+    fn image(&self, _change_count: isize) -> Option<String> {
+        None
+    }
+
+    fn set_text(&self, _text: &str) -> Result<isize, String> {
+        Err("Nothing can be copied in tests".to_string())
+    }
+}
+
+#[cfg(test)]
+impl std::ops::Deref for TestCapture {
+    type Target = Arc<Capture>;
+
+    fn deref(&self) -> &Arc<Capture> {
+        &self.capture
+    }
+}
+
+#[cfg(test)]
+impl Drop for TestCapture {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.dir);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_capture_lock_is_exclusive() {
+        let db_path = std::env::temp_dir().join(format!("clipboard_capture_lock_{}.db", std::process::id()));
+
+        let lock = CaptureLock::try_acquire(&db_path).unwrap();
+        assert!(lock.is_some());
+        assert!(CaptureLock::try_acquire(&db_path).unwrap().is_none());
+
+        // Released when the holder goes away
+        drop(lock);
+        assert!(CaptureLock::try_acquire(&db_path).unwrap().is_some());
+
+        let _ = std::fs::remove_file(db_path.with_extension("lock"));
+    }
+
+    #[test]
+    fn test_follower_pauses_the_capturing_process() {
+        let capturing = TestCapture::new("capture_follow");
+        ipc::IpcServer::attach(Arc::clone(&capturing), Arc::new(crate::search::SearchEngine::new()));
+        capturing.start_exclusive().unwrap();
+
+        let following = capturing.open_again();
+        let (sender, receiver) = std::sync::mpsc::channel();
+        following.subscribe(move |event| {
+            if let CaptureEvent::PauseChanged(status) = event {
+                let _ = sender.send(status.paused);
+            }
+        });
+        assert!(!following.start().unwrap());
+
+        following.pause().unwrap();
+        let now = chrono::Utc::now();
+        assert!(capturing.pause.is_paused(now));
+        assert!(following.pause.is_paused(now));
+        // The capturing process's event reaches the follower's subscribers
+        while !receiver.recv_timeout(Duration::from_secs(5)).unwrap() {}
+
+        following.resume().unwrap();
+        assert!(!capturing.pause.is_paused(chrono::Utc::now()));
+        while receiver.recv_timeout(Duration::from_secs(5)).unwrap() {}
+    }
+}
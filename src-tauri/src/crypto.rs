@@ -0,0 +1,264 @@
+use aes_gcm::aead::rand_core::RngCore;
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use argon2::Argon2;
+use base64::{engine::general_purpose, Engine as _};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+
+/// Prefix of every value produced by Cipher::encrypt
+pub const ENCRYPTED_PREFIX: &str = "enc:v1:";
+const KEY_LENGTH: usize = 32;
+const NONCE_LENGTH: usize = 12;
+pub const SALT_LENGTH: usize = 16;
+
+#[derive(Debug)]
+pub enum CryptoError {
+    /// Encryption is enabled but no key has been loaded yet
+    Locked,
+    AlreadyEnabled,
+    NotEnabled,
+    WrongKey,
+    InvalidKeyFile(String),
+    Malformed,
+}
+
+impl fmt::Display for CryptoError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CryptoError::Locked => write!(f, "Database is locked"),
+            CryptoError::AlreadyEnabled => write!(f, "Encryption is already enabled"),
+            CryptoError::NotEnabled => write!(f, "Encryption is not enabled"),
+            CryptoError::WrongKey => write!(f, "Wrong passphrase or key"),
+            CryptoError::InvalidKeyFile(reason) => write!(f, "Invalid key file: {}", reason),
+            CryptoError::Malformed => write!(f, "Encrypted value is malformed"),
+        }
+    }
+}
+
+impl std::error::Error for CryptoError {}
+
+impl From<CryptoError> for rusqlite::Error {
+    fn from(e: CryptoError) -> Self {
+        // Surfaces through rusqlite with the crypto error as its message
+        rusqlite::Error::ToSqlConversionFailure(Box::new(e))
+    }
+}
+
+/// Where the encryption key comes from
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum KeySource {
+    Passphrase(String),
+    /// Base64-encoded 32-byte key; created if the file doesn't exist
+    KeyFile(PathBuf),
+}
+
+/// AES-256-GCM cipher for stored text and image payloads
+pub struct Cipher {
+    aead: Aes256Gcm,
+}
+
+impl Cipher {
+    pub fn from_passphrase(passphrase: &str, salt: &[u8]) -> Result<Self, CryptoError> {
+        let mut key = [0u8; KEY_LENGTH];
+        Argon2::default()
+            .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+            .map_err(|_| CryptoError::WrongKey)?;
+        Ok(Self::from_key(&key))
+    }
+
+    /// Reads the key from a file, generating a new one if the file doesn't exist
+    pub fn from_key_file(path: &Path) -> Result<Self, CryptoError> {
+        if !path.exists() {
+            let mut key = [0u8; KEY_LENGTH];
+            OsRng.fill_bytes(&mut key);
+            write_key_file(path, &general_purpose::STANDARD.encode(key))
+                .map_err(|e| CryptoError::InvalidKeyFile(e.to_string()))?;
+        }
+
+        let contents = std::fs::read_to_string(path).map_err(|e| CryptoError::InvalidKeyFile(e.to_string()))?;
+        let key = general_purpose::STANDARD
+            .decode(contents.trim())
+            .map_err(|e| CryptoError::InvalidKeyFile(e.to_string()))?;
+        if key.len() != KEY_LENGTH {
+            return Err(CryptoError::InvalidKeyFile(format!("expected {} bytes, found {}", KEY_LENGTH, key.len())));
+        }
+        Ok(Self::from_key(&key))
+    }
+
+    fn from_key(key: &[u8]) -> Self {
+        Self { aead: Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(key)) }
+    }
+
+    /// Encrypts a value into the stored `enc:v1:<base64(nonce || ciphertext)>` form
+    pub fn encrypt(&self, plaintext: &str) -> Result<String, CryptoError> {
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let ciphertext = self.aead.encrypt(&nonce, plaintext.as_bytes()).map_err(|_| CryptoError::Malformed)?;
+
+        let mut payload = nonce.to_vec();
+        payload.extend_from_slice(&ciphertext);
+        Ok(format!("{}{}", ENCRYPTED_PREFIX, general_purpose::STANDARD.encode(payload)))
+    }
+
+    /// Decrypts a value produced by encrypt
+    pub fn decrypt(&self, stored: &str) -> Result<String, CryptoError> {
+        let encoded = stored.strip_prefix(ENCRYPTED_PREFIX).ok_or(CryptoError::Malformed)?;
+
+        let payload = general_purpose::STANDARD.decode(encoded).map_err(|_| CryptoError::Malformed)?;
+        if payload.len() < NONCE_LENGTH {
+            return Err(CryptoError::Malformed);
+        }
+        let (nonce, ciphertext) = payload.split_at(NONCE_LENGTH);
+        let plaintext = self.aead
+            .decrypt(Nonce::from_slice(nonce), ciphertext)
+            .map_err(|_| CryptoError::WrongKey)?;
+        String::from_utf8(plaintext).map_err(|_| CryptoError::Malformed)
+    }
+}
+
+pub fn generate_salt() -> [u8; SALT_LENGTH] {
+    let mut salt = [0u8; SALT_LENGTH];
+    OsRng.fill_bytes(&mut salt);
+    salt
+}
+
+/// Random URL-safe token for authenticating local API clients
+pub fn generate_token() -> String {
+    let mut token = [0u8; KEY_LENGTH];
+    OsRng.fill_bytes(&mut token);
+    general_purpose::URL_SAFE_NO_PAD.encode(token)
+}
+
+/// Compares secrets in time independent of where they first differ
+pub fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+/// HMAC-SHA256 of a message (RFC 2104), used to sign webhook deliveries
+pub fn hmac_sha256(key: &[u8], message: &[u8]) -> [u8; 32] {
+    const BLOCK_SIZE: usize = 64;
+    let mut block = [0u8; BLOCK_SIZE];
+    if key.len() > BLOCK_SIZE {
+        block[..32].copy_from_slice(&Sha256::digest(key));
+    } else {
+        block[..key.len()].copy_from_slice(key);
+    }
+    let inner_pad: Vec<u8> = block.iter().map(|byte| byte ^ 0x36).collect();
+    let outer_pad: Vec<u8> = block.iter().map(|byte| byte ^ 0x5c).collect();
+    let inner = Sha256::new().chain_update(inner_pad).chain_update(message).finalize();
+    Sha256::new().chain_update(outer_pad).chain_update(inner).finalize().into()
+}
+
+fn write_key_file(path: &Path, contents: &str) -> std::io::Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+
+    #[cfg(unix)]
+    {
+        use std::io::Write;
+        use std::os::unix::fs::OpenOptionsExt;
+        // Only the current user may read the key
+        let mut file = std::fs::OpenOptions::new().write(true).create_new(true).mode(0o600).open(path)?;
+        file.write_all(contents.as_bytes())
+    }
+
+    #[cfg(not(unix))]
+    {
+        std::fs::write(path, contents)
+    }
+}
+
+/// Cipher shared by every connection to the same database, None while locked or unencrypted
+#[derive(Clone, Default)]
+pub struct KeySlot(Arc<RwLock<Option<Arc<Cipher>>>>);
+
+impl KeySlot {
+    pub fn get(&self) -> Option<Arc<Cipher>> {
+        self.0.read().unwrap().clone()
+    }
+
+    pub fn set(&self, cipher: Option<Arc<Cipher>>) {
+        *self.0.write().unwrap() = cipher;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encrypt_decrypt_round_trip() {
+        let cipher = Cipher::from_passphrase("correct horse", &generate_salt()).unwrap();
+
+        let stored = cipher.encrypt("secret text").unwrap();
+        assert!(stored.starts_with(ENCRYPTED_PREFIX));
+        assert!(!stored.contains("secret"));
+        assert_eq!(cipher.decrypt(&stored).unwrap(), "secret text");
+    }
+
+    #[test]
+    fn test_encryption_uses_fresh_nonce() {
+        let cipher = Cipher::from_passphrase("correct horse", &generate_salt()).unwrap();
+
+        assert_ne!(cipher.encrypt("same").unwrap(), cipher.encrypt("same").unwrap());
+    }
+
+    #[test]
+    fn test_plaintext_is_rejected() {
+        let cipher = Cipher::from_passphrase("correct horse", &generate_salt()).unwrap();
+
+        assert!(matches!(cipher.decrypt("not encrypted"), Err(CryptoError::Malformed)));
+    }
+
+    #[test]
+    fn test_wrong_passphrase_fails() {
+        let salt = generate_salt();
+        let stored = Cipher::from_passphrase("correct horse", &salt).unwrap().encrypt("secret").unwrap();
+        let wrong = Cipher::from_passphrase("battery staple", &salt).unwrap();
+
+        assert!(matches!(wrong.decrypt(&stored), Err(CryptoError::WrongKey)));
+    }
+
+    #[test]
+    fn test_key_file_is_created_and_reused() {
+        let path = std::env::temp_dir().join(format!("clipboard_key_{}.key", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        let stored = Cipher::from_key_file(&path).unwrap().encrypt("secret").unwrap();
+        assert_eq!(Cipher::from_key_file(&path).unwrap().decrypt(&stored).unwrap(), "secret");
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_tokens_are_unique() {
+        let token = generate_token();
+        assert_eq!(token.len(), 43);
+        assert_ne!(token, generate_token());
+
+        assert!(constant_time_eq(token.as_bytes(), token.as_bytes()));
+        assert!(!constant_time_eq(token.as_bytes(), b"short"));
+    }
+
+    fn hex(bytes: &[u8]) -> String {
+        bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+    }
+
+    #[test]
+    fn test_hmac_sha256() {
+        // RFC 4231 test cases 2 and 6
+        assert_eq!(
+            hex(&hmac_sha256(b"Jefe", b"what do ya want for nothing?")),
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+        assert_eq!(
+            hex(&hmac_sha256(&[0xaa; 131], b"Test Using Larger Than Block-Size Key - Hash Key First")),
+            "60e431591ee0b67f0d8a26aacbf5b77f8e0bc6213728c5140546040f0ee37f54"
+        );
+    }
+}
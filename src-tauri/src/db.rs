@@ -1,20 +1,75 @@
-use rusqlite::{Connection, Result};
+use base64::{engine::general_purpose, Engine as _};
+use rusqlite::{Connection, OpenFlags, OptionalExtension, Result, Row};
 use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-enum ContentType {
+use crate::crypto::{generate_salt, Cipher, CryptoError, KeySlot, KeySource};
+
+/// Maximum number of characters of text kept in an entry summary
+pub const PREVIEW_LENGTH: usize = 200;
+/// Largest page list_entries returns, whatever limit is asked for
+pub const MAX_PAGE_SIZE: usize = 500;
+/// Name of the database file in the app data directory
+pub const DB_FILE_NAME: &str = "clipboard_history.db";
+
+// Keys of the app_meta table used by encryption
+const META_ENCRYPTION_CHECK: &str = "encryption_check";
+const META_ENCRYPTION_SALT: &str = "encryption_salt";
+const META_ENCRYPTION_KEY_FILE: &str = "encryption_key_file";
+// Known plaintext stored encrypted to verify a passphrase or key
+const ENCRYPTION_CHECK_PLAINTEXT: &str = "clipboardwatcher";
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ContentType {
     Text,
     Image,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+impl ContentType {
+    /// Value stored in the content_type column
+    pub fn as_db_str(&self) -> &'static str {
+        match self {
+            ContentType::Text => "TEXT",
+            ContentType::Image => "IMAGE",
+        }
+    }
+
+    pub fn from_db_str(value: &str) -> Self {
+        if value == "TEXT" {
+            ContentType::Text
+        } else {
+            ContentType::Image
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct ClipboardEntry {
     pub id: Option<i64>,
     pub content_type: ContentType,
     pub image_path: Option<String>,
     pub text_content: Option<String>,
     pub created_at: String,
+    /// Unix time after which the entry is deleted automatically
+    #[serde(default)]
+    pub expires_at: Option<i64>,
+    /// Bundle id (macOS) or WM_CLASS (X11) of the application the copy came from
+    #[serde(default)]
+    pub source_app_id: Option<String>,
+    #[serde(default)]
+    pub source_app_name: Option<String>,
+    /// Title of the source application's frontmost window at the time of the copy
+    #[serde(default)]
+    pub source_window_title: Option<String>,
+    /// Pinned entries are kept regardless of the history limits
+    #[serde(default)]
+    pub pinned: bool,
+    /// Set on a new copy the secret filter masked or expired. Only kept until
+    /// the entry is stored, so it's false on entries read from the database.
+    #[serde(skip)]
+    pub flagged: bool,
 }
 
 impl ClipboardEntry {
@@ -25,6 +80,12 @@ impl ClipboardEntry {
             text_content: Some(text),
             image_path: None,
             created_at: chrono::Utc::now().to_string(),
+            expires_at: None,
+            source_app_id: None,
+            source_app_name: None,
+            source_window_title: None,
+            pinned: false,
+            flagged: false,
         }
     }
 
@@ -35,23 +96,169 @@ impl ClipboardEntry {
             image_path: Some(image_path),
             text_content: None,
             created_at: chrono::Utc::now().to_string(),
+            expires_at: None,
+            source_app_id: None,
+            source_app_name: None,
+            source_window_title: None,
+            pinned: false,
+            flagged: false,
         }
     }
+
+    /// PNG bytes of an image entry, which stores them as a data URL
+    pub fn image_bytes(&self) -> Result<Vec<u8>, String> {
+        let data_url = self.image_path.as_deref().ok_or("Entry has no image".to_string())?;
+        let encoded = data_url.split_once(";base64,").map(|(_, data)| data).ok_or("Unsupported image format".to_string())?;
+        general_purpose::STANDARD.decode(encoded).map_err(|e| e.to_string())
+    }
+}
+
+/// Lightweight view of an entry for listing. Image data is not included;
+/// fetch the full entry by id when it needs to be displayed.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EntrySummary {
+    pub id: i64,
+    pub content_type: ContentType,
+    /// First PREVIEW_LENGTH characters of the text
+    pub preview: Option<String>,
+    pub truncated: bool,
+    pub created_at: String,
+    pub source_app_id: Option<String>,
+    pub source_app_name: Option<String>,
+    pub pinned: bool,
+    /// Path of an image entry's PNG, `entries/<id>/image`, served by the HTTP API
+    /// and the app's `entry` URI scheme so the image is only loaded when shown
+    pub thumbnail: Option<String>,
+}
+
+/// Number of stored entries by type
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EntryCounts {
+    pub text: i64,
+    pub image: i64,
+}
+
+/// Position after the last entry of a page, ordered by (created_at, id) descending
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EntryCursor {
+    pub created_at: String,
+    pub id: i64,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EntryFilter {
+    pub content_type: Option<ContentType>,
+    /// Bundle id / WM_CLASS or application name, compared case-insensitively.
+    /// Names are stored encrypted along with the content, so encrypted entries only match by bundle id.
+    pub source_app: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EntryPage {
+    pub entries: Vec<EntrySummary>,
+    /// None when this is the last page
+    pub next_cursor: Option<EntryCursor>,
+}
+
+/// Columns read by map_entry_row, in order
+const ENTRY_COLUMNS: &str =
+    "id, content_type, text_content, image_path, created_at, expires_at, source_app_id, source_app_name, source_window_title, pinned, encrypted";
+
+/// Maps a row selected with ENTRY_COLUMNS to an entry and whether its payloads are encrypted
+fn map_entry_row(row: &Row) -> Result<(ClipboardEntry, bool)> {
+    let content_type_str: String = row.get(1)?;
+    let entry = ClipboardEntry {
+        id: row.get(0)?,
+        content_type: ContentType::from_db_str(&content_type_str),
+        text_content: row.get(2)?,
+        image_path: row.get(3)?,
+        created_at: row.get(4)?,
+        expires_at: row.get(5)?,
+        source_app_id: row.get(6)?,
+        source_app_name: row.get(7)?,
+        source_window_title: row.get(8)?,
+        pinned: row.get(9)?,
+        flagged: false,
+    };
+    Ok((entry, row.get(10)?))
+}
+
+/// Columns read by map_summary_row, in order. ?1 is PREVIEW_LENGTH.
+/// Encrypted text is selected whole and truncated once decrypted.
+const SUMMARY_COLUMNS: &str = "id, content_type,
+    CASE WHEN encrypted THEN text_content ELSE substr(text_content, 1, ?1) END,
+    length(text_content) > ?1, created_at, source_app_id, source_app_name, pinned, encrypted";
+
+/// Maps a row selected with SUMMARY_COLUMNS to a summary and whether its payloads are encrypted
+fn map_summary_row(row: &Row) -> Result<(EntrySummary, bool)> {
+    let id: i64 = row.get(0)?;
+    let content_type = ContentType::from_db_str(&row.get::<_, String>(1)?);
+    let summary = EntrySummary {
+        id,
+        thumbnail: (content_type == ContentType::Image).then(|| format!("entries/{}/image", id)),
+        content_type,
+        preview: row.get(2)?,
+        truncated: row.get::<_, Option<bool>>(3)?.unwrap_or(false),
+        created_at: row.get(4)?,
+        source_app_id: row.get(5)?,
+        source_app_name: row.get(6)?,
+        pinned: row.get(7)?,
+    };
+    Ok((summary, row.get(8)?))
+}
+
+/// An entry waiting in the webhook queue
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QueuedWebhook {
+    pub entry_id: i64,
+    /// Failed attempts so far
+    pub attempts: u32,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct EncryptionStatus {
+    pub enabled: bool,
+    /// Whether the key is loaded, so entries can be read and saved
+    pub unlocked: bool,
 }
 
 pub struct ClipboardDatabase {
     conn: Connection,
+    keys: KeySlot,
 }
 
 impl ClipboardDatabase {
     /// Creates a new database connection and initializes the schema
     pub fn new(db_path: PathBuf) -> Result<Self> {
         let conn = Connection::open(db_path)?;
-        let db = ClipboardDatabase { conn };
+        conn.busy_timeout(Duration::from_secs(5))?;
+        // WAL lets read-only connections query while the writer inserts
+        conn.pragma_update_and_check(None, "journal_mode", "WAL", |row| row.get::<_, String>(0))?;
+        let db = ClipboardDatabase { conn, keys: KeySlot::default() };
         db.init_schema()?;
         Ok(db)
     }
 
+    /// Opens a read-only connection to a database created by `new`
+    pub fn open_read_only(db_path: PathBuf) -> Result<Self> {
+        let conn = Connection::open_with_flags(
+            db_path,
+            OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX,
+        )?;
+        conn.busy_timeout(Duration::from_secs(5))?;
+        Ok(ClipboardDatabase { conn, keys: KeySlot::default() })
+    }
+
+    /// Shares the encryption key with other connections to the same database
+    pub fn with_keys(mut self, keys: KeySlot) -> Self {
+        self.keys = keys;
+        self
+    }
+
+    pub fn keys(&self) -> &KeySlot {
+        &self.keys
+    }
+
     /// Creates the clipboard_history table if it doesn't exist
     fn init_schema(&self) -> Result<()> {
         self.conn.execute(
@@ -71,80 +278,326 @@ impl ClipboardDatabase {
             [],
         )?;
 
+        // Covers the keyset pagination order of list_entries
+        self.conn.execute(
+            "CREATE INDEX IF NOT EXISTS idx_created_at_id ON clipboard_history(created_at DESC, id DESC)",
+            [],
+        )?;
+
+        self.add_column_if_missing("clipboard_history", "expires_at", "INTEGER")?;
+        self.add_column_if_missing("clipboard_history", "source_app_id", "TEXT")?;
+        self.add_column_if_missing("clipboard_history", "source_app_name", "TEXT")?;
+        self.add_column_if_missing("clipboard_history", "source_window_title", "TEXT")?;
+        self.add_column_if_missing("clipboard_history", "pinned", "INTEGER NOT NULL DEFAULT 0")?;
+
+        self.conn.execute(
+            "CREATE TABLE IF NOT EXISTS app_meta (
+                key TEXT PRIMARY KEY,
+                value TEXT NOT NULL
+            )",
+            [],
+        )?;
+
+        // Whether the row's content, source app name and window title are stored encrypted
+        self.add_column_if_missing("clipboard_history", "encrypted", "INTEGER NOT NULL DEFAULT 0")?;
+
+        // Entries waiting to be sent to the webhook, kept across restarts
+        self.conn.execute(
+            "CREATE TABLE IF NOT EXISTS webhook_queue (
+                entry_id INTEGER PRIMARY KEY,
+                attempts INTEGER NOT NULL DEFAULT 0,
+                next_attempt_at INTEGER NOT NULL,
+                last_error TEXT
+            )",
+            [],
+        )?;
+
+        Ok(())
+    }
+
+    /// Adds a column to a table created by an older version of the schema
+    fn add_column_if_missing(&self, table: &str, column: &str, definition: &str) -> Result<()> {
+        let exists: bool = self.conn.query_row(
+            &format!("SELECT COUNT(*) > 0 FROM pragma_table_info('{}') WHERE name = ?1", table),
+            [column],
+            |row| row.get(0),
+        )?;
+        if !exists {
+            self.conn.execute(&format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, definition), [])?;
+        }
         Ok(())
     }
 
+    fn get_meta(&self, key: &str) -> Result<Option<String>> {
+        self.conn.query_row("SELECT value FROM app_meta WHERE key = ?1", [key], |row| row.get(0)).optional()
+    }
+
+    fn set_meta(&self, key: &str, value: &str) -> Result<()> {
+        self.conn.execute(
+            "INSERT INTO app_meta (key, value) VALUES (?1, ?2) ON CONFLICT(key) DO UPDATE SET value = excluded.value",
+            [key, value],
+        )?;
+        Ok(())
+    }
+
+    /// Cipher new rows are sealed with, None while encryption is disabled
+    fn sealing_cipher(&self) -> Result<Option<Arc<Cipher>>> {
+        match self.keys.get() {
+            Some(cipher) => Ok(Some(cipher)),
+            // Never fall back to plaintext while the key isn't loaded
+            None if self.get_meta(META_ENCRYPTION_CHECK)?.is_some() => Err(CryptoError::Locked.into()),
+            None => Ok(None),
+        }
+    }
+
+    /// Encrypts a payload for storage when a cipher is given
+    fn seal(cipher: Option<&Cipher>, value: Option<&String>) -> Result<Option<String>> {
+        match (cipher, value) {
+            (Some(cipher), Some(value)) => Ok(Some(cipher.encrypt(value)?)),
+            (_, value) => Ok(value.cloned()),
+        }
+    }
+
+    /// Decrypts a column of a row stored encrypted; plaintext rows are returned unchanged
+    fn open(&self, encrypted: bool, value: Option<String>) -> Result<Option<String>> {
+        match value {
+            Some(value) if encrypted => {
+                let cipher = self.keys.get().ok_or(CryptoError::Locked)?;
+                Ok(Some(cipher.decrypt(&value)?))
+            }
+            value => Ok(value),
+        }
+    }
+
+    fn open_entry(&self, (mut entry, encrypted): (ClipboardEntry, bool)) -> Result<ClipboardEntry> {
+        entry.text_content = self.open(encrypted, entry.text_content)?;
+        entry.image_path = self.open(encrypted, entry.image_path)?;
+        entry.source_app_name = self.open(encrypted, entry.source_app_name)?;
+        entry.source_window_title = self.open(encrypted, entry.source_window_title)?;
+        Ok(entry)
+    }
+
+    fn open_summary(&self, (mut summary, encrypted): (EntrySummary, bool)) -> Result<EntrySummary> {
+        summary.source_app_name = self.open(encrypted, summary.source_app_name)?;
+        // Encrypted previews come back whole and are truncated after decryption
+        if encrypted && summary.preview.is_some() {
+            let text = self.open(encrypted, summary.preview)?.unwrap_or_default();
+            summary.truncated = text.chars().count() > PREVIEW_LENGTH;
+            summary.preview = Some(text.chars().take(PREVIEW_LENGTH).collect());
+        }
+        Ok(summary)
+    }
+
     /// Saves a clipboard entry to the database
     pub fn save_entry(&self, clipboard_entry: ClipboardEntry) -> Result<i64>
     {
+        let cipher = self.sealing_cipher()?;
+        let source_app_name = Self::seal(cipher.as_deref(), clipboard_entry.source_app_name.as_ref())?;
+        let source_window_title = Self::seal(cipher.as_deref(), clipboard_entry.source_window_title.as_ref())?;
         match clipboard_entry.content_type {
             ContentType::Text => {
+                let text_content = Self::seal(cipher.as_deref(), clipboard_entry.text_content.as_ref())?;
                 self.conn.execute(
-                    "INSERT INTO clipboard_history (content_type, text_content, created_at) VALUES (?1, ?2, ?3)",
-                    rusqlite::params!["TEXT", &clipboard_entry.text_content, &clipboard_entry.created_at],
+                    "INSERT INTO clipboard_history (content_type, text_content, created_at, expires_at,
+                                                    source_app_id, source_app_name, source_window_title, encrypted)
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+                    rusqlite::params![
+                        "TEXT",
+                        &text_content,
+                        &clipboard_entry.created_at,
+                        &clipboard_entry.expires_at,
+                        &clipboard_entry.source_app_id,
+                        &source_app_name,
+                        &source_window_title,
+                        cipher.is_some(),
+                    ],
                 )?;
             }
             ContentType::Image => {
+                let image_path = Self::seal(cipher.as_deref(), clipboard_entry.image_path.as_ref())?;
                 self.conn.execute(
-                    "INSERT INTO clipboard_history (content_type, image_path, created_at) VALUES (?1, ?2, ?3)",
-                    rusqlite::params!["IMAGE", &clipboard_entry.image_path, &clipboard_entry.created_at],
+                    "INSERT INTO clipboard_history (content_type, image_path, created_at, expires_at,
+                                                    source_app_id, source_app_name, source_window_title, encrypted)
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+                    rusqlite::params![
+                        "IMAGE",
+                        &image_path,
+                        &clipboard_entry.created_at,
+                        &clipboard_entry.expires_at,
+                        &clipboard_entry.source_app_id,
+                        &source_app_name,
+                        &source_window_title,
+                        cipher.is_some(),
+                    ],
                 )?;
             }
         }
         Ok(self.conn.last_insert_rowid())
     }
 
+    /// Saves several entries in a single transaction, returning their ids in order
+    pub fn save_entries(&self, entries: &[ClipboardEntry]) -> Result<Vec<i64>> {
+        let tx = self.conn.unchecked_transaction()?;
+        let ids = entries
+            .iter()
+            .map(|entry| self.save_entry(entry.clone()))
+            .collect::<Result<Vec<_>>>()?;
+        tx.commit()?;
+        Ok(ids)
+    }
+
     /// Retrieves all clipboard entries, sorted by most recent first
     pub fn get_all_entries(&self) -> Result<Vec<ClipboardEntry>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {} FROM clipboard_history ORDER BY created_at DESC, id DESC",
+            ENTRY_COLUMNS
+        ))?;
+
+        let entries = stmt.query_map([], map_entry_row)?;
+        entries.map(|entry| self.open_entry(entry?)).collect()
+    }
+
+    /// Retrieves the latest N clipboard entries
+    pub fn get_recent_entries(&self, limit: usize) -> Result<Vec<ClipboardEntry>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {} FROM clipboard_history ORDER BY created_at DESC, id DESC LIMIT ?1",
+            ENTRY_COLUMNS
+        ))?;
+
+        let entries = stmt.query_map([limit as i64], map_entry_row)?;
+        entries.map(|entry| self.open_entry(entry?)).collect()
+    }
+
+    /// Id and text of every text entry, most recent first. Builds the search
+    /// index without loading any images.
+    pub fn get_text_contents(&self) -> Result<Vec<(i64, String)>> {
         let mut stmt = self.conn.prepare(
-            "SELECT id, content_type, text_content, image_path, created_at FROM clipboard_history ORDER BY created_at DESC"
+            "SELECT id, text_content, encrypted FROM clipboard_history
+             WHERE content_type = 'TEXT' AND text_content IS NOT NULL
+             ORDER BY created_at DESC, id DESC"
         )?;
 
-        let entries = stmt.query_map([], |row| {
-            let id: i64 = row.get(0)?;
-            let content_type_str: String = row.get(1)?;
-            let content_type = if content_type_str == "TEXT" {
-                ContentType::Text
-            } else {
-                ContentType::Image
-            };
-
-            Ok(ClipboardEntry {
-                id: Some(id),
-                content_type,
-                text_content: row.get(2)?,
-                image_path: row.get(3)?,
-                created_at: row.get(4)?,
-            })
+        let rows = stmt.query_map([], |row| {
+            Ok((row.get::<_, i64>(0)?, row.get::<_, Option<String>>(1)?, row.get::<_, bool>(2)?))
         })?;
+        rows.map(|row| {
+            let (id, text, encrypted) = row?;
+            Ok((id, self.open(encrypted, text)?.unwrap_or_default()))
+        })
+        .collect()
+    }
 
-        entries.collect()
+    pub fn count_entries_by_type(&self) -> Result<EntryCounts> {
+        self.conn.query_row(
+            "SELECT COALESCE(SUM(content_type = 'TEXT'), 0), COALESCE(SUM(content_type = 'IMAGE'), 0) FROM clipboard_history",
+            [],
+            |row| Ok(EntryCounts { text: row.get(0)?, image: row.get(1)? }),
+        )
     }
 
-    /// Retrieves the latest N clipboard entries
-    pub fn get_recent_entries(&self, limit: usize) -> Result<Vec<ClipboardEntry>> {
-        let mut stmt = self.conn.prepare(
-            "SELECT id, content_type, text_content, image_path, created_at FROM clipboard_history ORDER BY created_at DESC LIMIT ?1"
+    /// Retrieves entries inserted after the given id, oldest first
+    pub fn get_entries_after(&self, id: i64) -> Result<Vec<ClipboardEntry>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {} FROM clipboard_history WHERE id > ?1 ORDER BY id ASC",
+            ENTRY_COLUMNS
+        ))?;
+
+        let entries = stmt.query_map([id], map_entry_row)?;
+        entries.map(|entry| self.open_entry(entry?)).collect()
+    }
+
+    /// Id of the most recently inserted entry, 0 if there is none
+    pub fn latest_id(&self) -> Result<i64> {
+        self.conn.query_row("SELECT COALESCE(MAX(id), 0) FROM clipboard_history", [], |row| row.get(0))
+    }
+
+    pub fn count_entries(&self) -> Result<i64> {
+        self.conn.query_row("SELECT COUNT(*) FROM clipboard_history", [], |row| row.get(0))
+    }
+
+    /// Summaries of the pinned entries, newest first
+    pub fn get_pinned_summaries(&self) -> Result<Vec<EntrySummary>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {} FROM clipboard_history WHERE pinned = 1 ORDER BY created_at DESC, id DESC",
+            SUMMARY_COLUMNS
+        ))?;
+
+        let summaries = stmt.query_map([PREVIEW_LENGTH as i64], map_summary_row)?;
+        summaries.map(|summary| self.open_summary(summary?)).collect()
+    }
+
+    /// Pins or unpins an entry. Returns false if there is no entry with that id.
+    pub fn set_pinned(&self, id: i64, pinned: bool) -> Result<bool> {
+        let updated = self.conn.execute(
+            "UPDATE clipboard_history SET pinned = ?2 WHERE id = ?1",
+            rusqlite::params![id, pinned],
         )?;
+        Ok(updated > 0)
+    }
+
+    /// Retrieves a single entry by ID
+    pub fn get_entry(&self, id: i64) -> Result<Option<ClipboardEntry>> {
+        self.conn.query_row(
+            &format!("SELECT {} FROM clipboard_history WHERE id = ?1", ENTRY_COLUMNS),
+            [id],
+            map_entry_row,
+        ).optional()?
+            .map(|entry| self.open_entry(entry))
+            .transpose()
+    }
 
-        let entries = stmt.query_map([limit], |row| {
-            let content_type_str: String = row.get(0)?;
-            let content_type = if content_type_str == "TEXT" {
-                ContentType::Text
-            } else {
-                ContentType::Image
-            };
-
-            Ok(ClipboardEntry {
-                id: row.get(0)?,
-                content_type,
-                text_content: row.get(1)?,
-                image_path: row.get(2)?,
-                created_at: row.get(3)?,
+    /// Lists entry summaries, most recent first, starting after the cursor.
+    /// Uses keyset pagination so each page costs the same regardless of depth.
+    /// The limit is clamped to 1..=MAX_PAGE_SIZE.
+    pub fn list_entries(&self, cursor: Option<&EntryCursor>, limit: usize, filter: &EntryFilter) -> Result<EntryPage> {
+        let limit = limit.clamp(1, MAX_PAGE_SIZE);
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {} FROM clipboard_history
+             WHERE (?2 IS NULL OR (created_at, id) < (?2, ?3))
+               AND (?4 IS NULL OR content_type = ?4)
+               AND (?6 IS NULL OR source_app_id = ?6 COLLATE NOCASE OR source_app_name = ?6 COLLATE NOCASE)
+             ORDER BY created_at DESC, id DESC
+             LIMIT ?5",
+            SUMMARY_COLUMNS
+        ))?;
+
+        // Fetch one extra row to find out whether another page follows
+        let params = rusqlite::params![
+            PREVIEW_LENGTH as i64,
+            cursor.map(|c| &c.created_at),
+            cursor.map(|c| c.id),
+            filter.content_type.as_ref().map(|t| t.as_db_str()),
+            limit as i64 + 1,
+            filter.source_app.as_deref(),
+        ];
+        let mut entries = stmt
+            .query_map(params, map_summary_row)?
+            .map(|summary| self.open_summary(summary?))
+            .collect::<Result<Vec<_>>>()?;
+
+        let next_cursor = if entries.len() > limit {
+            entries.truncate(limit);
+            entries.last().map(|last| EntryCursor {
+                created_at: last.created_at.clone(),
+                id: last.id,
             })
-        })?;
+        } else {
+            None
+        };
 
-        entries.collect()
+        Ok(EntryPage { entries, next_cursor })
+    }
+
+    /// Summaries of the given entries in the same order, skipping ids that don't exist
+    pub fn get_summaries(&self, ids: &[i64]) -> Result<Vec<EntrySummary>> {
+        let mut stmt = self.conn.prepare(&format!("SELECT {} FROM clipboard_history WHERE id = ?2", SUMMARY_COLUMNS))?;
+        let mut summaries = Vec::with_capacity(ids.len());
+        for &id in ids {
+            if let Some(summary) = stmt.query_row(rusqlite::params![PREVIEW_LENGTH as i64, id], map_summary_row).optional()? {
+                summaries.push(self.open_summary(summary)?);
+            }
+        }
+        Ok(summaries)
     }
 
     /// Deletes an entry by ID
@@ -158,21 +611,218 @@ impl ClipboardDatabase {
         }
     }
 
+    /// Deletes entries whose expiry time has passed, returning their ids
+    pub fn delete_expired_entries(&self, now: i64) -> Result<Vec<i64>> {
+        let mut stmt = self.conn.prepare(
+            "DELETE FROM clipboard_history WHERE expires_at IS NOT NULL AND expires_at <= ?1 RETURNING id"
+        )?;
+        let ids = stmt.query_map([now], |row| row.get(0))?;
+        ids.collect()
+    }
+
+    /// Deletes entries beyond the newest `max_entries` and entries created before
+    /// `created_before` (in the created_at format), returning their ids.
+    /// Pinned entries are never deleted and don't count towards the limit.
+    pub fn prune_history(&self, max_entries: Option<usize>, created_before: Option<&str>) -> Result<Vec<i64>> {
+        let mut stmt = self.conn.prepare(
+            "DELETE FROM clipboard_history
+             WHERE pinned = 0
+               AND ((?2 IS NOT NULL AND created_at < ?2)
+                    OR id IN (SELECT id FROM clipboard_history WHERE pinned = 0
+                              ORDER BY created_at DESC, id DESC
+                              LIMIT -1 OFFSET ?1))
+             RETURNING id"
+        )?;
+        // An offset past the end selects nothing, so no limit keeps every entry
+        let offset = max_entries.map_or(i64::MAX, |max| max as i64);
+        let ids = stmt.query_map(rusqlite::params![offset, created_before], |row| row.get(0))?;
+        ids.collect()
+    }
+
+    /// Queues an entry for webhook delivery at `now`, unless it's already queued
+    pub fn queue_webhook(&self, entry_id: i64, now: i64) -> Result<()> {
+        self.conn.execute(
+            "INSERT OR IGNORE INTO webhook_queue (entry_id, next_attempt_at) VALUES (?1, ?2)",
+            [entry_id, now],
+        )?;
+        Ok(())
+    }
+
+    /// Queued deliveries whose next attempt is due, oldest entries first
+    pub fn due_webhooks(&self, now: i64, limit: usize) -> Result<Vec<QueuedWebhook>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT entry_id, attempts FROM webhook_queue WHERE next_attempt_at <= ?1 ORDER BY entry_id LIMIT ?2"
+        )?;
+        let queued = stmt.query_map([now, limit as i64], |row| {
+            Ok(QueuedWebhook { entry_id: row.get(0)?, attempts: row.get(1)? })
+        })?;
+        queued.collect()
+    }
+
+    /// Time of the earliest queued attempt, None if the queue is empty
+    pub fn next_webhook_attempt(&self) -> Result<Option<i64>> {
+        self.conn.query_row("SELECT MIN(next_attempt_at) FROM webhook_queue", [], |row| row.get(0))
+    }
+
+    pub fn remove_webhook(&self, entry_id: i64) -> Result<()> {
+        self.conn.execute("DELETE FROM webhook_queue WHERE entry_id = ?1", [entry_id])?;
+        Ok(())
+    }
+
+    /// Records a failed attempt and when to try again
+    pub fn retry_webhook(&self, entry_id: i64, next_attempt_at: i64, error: &str) -> Result<()> {
+        self.conn.execute(
+            "UPDATE webhook_queue SET attempts = attempts + 1, next_attempt_at = ?2, last_error = ?3 WHERE entry_id = ?1",
+            rusqlite::params![entry_id, next_attempt_at, error],
+        )?;
+        Ok(())
+    }
+
     /// Clears all clipboard history
     pub fn clear_all(&self) -> Result<()> {
         self.conn.execute("DELETE FROM clipboard_history", [])?;
         Ok(())
     }
+
+    pub fn encryption_status(&self) -> Result<EncryptionStatus> {
+        Ok(EncryptionStatus {
+            enabled: self.get_meta(META_ENCRYPTION_CHECK)?.is_some(),
+            unlocked: self.keys.get().is_some(),
+        })
+    }
+
+    /// Encrypts every stored entry and all future ones with a key from the given source
+    pub fn enable_encryption(&self, source: &KeySource) -> Result<()> {
+        if self.get_meta(META_ENCRYPTION_CHECK)?.is_some() {
+            return Err(CryptoError::AlreadyEnabled.into());
+        }
+
+        let tx = self.conn.unchecked_transaction()?;
+        let cipher = match source {
+            KeySource::Passphrase(passphrase) => {
+                let salt = generate_salt();
+                self.set_meta(META_ENCRYPTION_SALT, &general_purpose::STANDARD.encode(salt))?;
+                Cipher::from_passphrase(passphrase, &salt)?
+            }
+            KeySource::KeyFile(path) => {
+                self.set_meta(META_ENCRYPTION_KEY_FILE, &path.to_string_lossy())?;
+                Cipher::from_key_file(path)?
+            }
+        };
+        self.set_meta(META_ENCRYPTION_CHECK, &cipher.encrypt(ENCRYPTION_CHECK_PLAINTEXT)?)?;
+        self.rewrite_payloads(true, |value| Ok(cipher.encrypt(value)?))?;
+        tx.commit()?;
+
+        self.keys.set(Some(Arc::new(cipher)));
+        self.scrub_free_pages()
+    }
+
+    /// Decrypts every stored entry and stops encrypting new ones. Requires the key to be loaded.
+    pub fn disable_encryption(&self) -> Result<()> {
+        if self.get_meta(META_ENCRYPTION_CHECK)?.is_none() {
+            return Err(CryptoError::NotEnabled.into());
+        }
+        let cipher = self.keys.get().ok_or(CryptoError::Locked)?;
+
+        let tx = self.conn.unchecked_transaction()?;
+        self.rewrite_payloads(false, |value| Ok(cipher.decrypt(value)?))?;
+        self.conn.execute(
+            "DELETE FROM app_meta WHERE key IN (?1, ?2, ?3)",
+            [META_ENCRYPTION_CHECK, META_ENCRYPTION_SALT, META_ENCRYPTION_KEY_FILE],
+        )?;
+        tx.commit()?;
+
+        self.keys.set(None);
+        Ok(())
+    }
+
+    /// Drops the plaintext that rewritten rows leave behind in free pages and the WAL
+    fn scrub_free_pages(&self) -> Result<()> {
+        self.conn.execute_batch("VACUUM")?;
+        self.conn.query_row("PRAGMA wal_checkpoint(TRUNCATE)", [], |_| Ok(()))
+    }
+
+    /// Loads the key from the given source after checking it against the stored verifier
+    pub fn unlock(&self, source: &KeySource) -> Result<()> {
+        let check = self.get_meta(META_ENCRYPTION_CHECK)?.ok_or(CryptoError::NotEnabled)?;
+        let cipher = match source {
+            KeySource::Passphrase(passphrase) => {
+                let salt = self.get_meta(META_ENCRYPTION_SALT)?.ok_or(CryptoError::WrongKey)?;
+                let salt = general_purpose::STANDARD.decode(salt).map_err(|_| CryptoError::Malformed)?;
+                Cipher::from_passphrase(passphrase, &salt)?
+            }
+            KeySource::KeyFile(path) => {
+                // Never generate a new key when unlocking
+                if !path.exists() {
+                    return Err(CryptoError::InvalidKeyFile(format!("{} not found", path.display())).into());
+                }
+                Cipher::from_key_file(path)?
+            }
+        };
+
+        if cipher.decrypt(&check)? != ENCRYPTION_CHECK_PLAINTEXT {
+            return Err(CryptoError::WrongKey.into());
+        }
+        self.keys.set(Some(Arc::new(cipher)));
+        Ok(())
+    }
+
+    /// Unlocks with the key file recorded when encryption was enabled, if any.
+    /// Passphrase-protected databases stay locked until `unlock` is called.
+    pub fn unlock_with_stored_key_file(&self) -> Result<bool> {
+        match self.get_meta(META_ENCRYPTION_KEY_FILE)? {
+            Some(path) => {
+                self.unlock(&KeySource::KeyFile(PathBuf::from(path)))?;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    /// Transforms the payloads, source app name and window title of every row not
+    /// yet in the given encryption state, then marks it as in that state
+    fn rewrite_payloads(&self, encrypt: bool, transform: impl Fn(&str) -> Result<String>) -> Result<()> {
+        let rows = {
+            let mut stmt = self.conn.prepare(
+                "SELECT id, text_content, image_path, source_app_name, source_window_title
+                 FROM clipboard_history WHERE encrypted != ?1"
+            )?;
+            let rows = stmt.query_map([encrypt], |row| {
+                Ok((row.get::<_, i64>(0)?, [row.get::<_, Option<String>>(1)?, row.get(2)?, row.get(3)?, row.get(4)?]))
+            })?;
+            rows.collect::<Result<Vec<_>>>()?
+        };
+
+        let mut update = self.conn.prepare(
+            "UPDATE clipboard_history
+             SET text_content = ?2, image_path = ?3, source_app_name = ?4, source_window_title = ?5, encrypted = ?6
+             WHERE id = ?1"
+        )?;
+        for (id, columns) in rows {
+            let [text_content, image_path, source_app_name, source_window_title] =
+                columns.map(|value| value.map(|value| transform(&value)).transpose());
+            update.execute(rusqlite::params![
+                id,
+                text_content?,
+                image_path?,
+                source_app_name?,
+                source_window_title?,
+                encrypt,
+            ])?;
+        }
+        Ok(())
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::crypto::ENCRYPTED_PREFIX;
 
     fn create_test_db() -> ClipboardDatabase {
         // Use in-memory database for tests to avoid file permission issues
         let conn = Connection::open_in_memory().unwrap();
-        let db = ClipboardDatabase { conn };
+        let db = ClipboardDatabase { conn, keys: KeySlot::default() };
         db.init_schema().unwrap();
         db
     }
@@ -240,6 +890,36 @@ mod tests {
         // Get only 3 most recent
         let entries = db.get_recent_entries(3).unwrap();
         assert_eq!(entries.len(), 3);
+        assert_eq!(entries[0].text_content, Some("Entry 5".to_string()));
+        assert_eq!(entries[2].text_content, Some("Entry 3".to_string()));
+
+        cleanup_test_db();
+    }
+
+    #[test]
+    fn test_get_recent_entries_round_trip() {
+        let db = create_test_db();
+
+        let mut text = ClipboardEntry::new_text_entry("Text entry".to_string());
+        text.source_app_id = Some("com.apple.Safari".to_string());
+        text.source_app_name = Some("Safari".to_string());
+        text.source_window_title = Some("Example Domain".to_string());
+        text.id = Some(db.save_entry(text.clone()).unwrap());
+        std::thread::sleep(std::time::Duration::from_millis(10));
+        let mut image = ClipboardEntry::new_image_entry("data:image/png;base64,iVBORw0KGgo=".to_string());
+        image.id = Some(db.save_entry(image.clone()).unwrap());
+
+        let entries = db.get_recent_entries(2).unwrap();
+        assert_eq!(entries, vec![image.clone(), text.clone()]);
+        assert_eq!(entries[0].content_type, ContentType::Image);
+        assert_eq!(entries[0].text_content, None);
+        assert_eq!(entries[1].content_type, ContentType::Text);
+        assert_eq!(entries[1].image_path, None);
+
+        // Every select shares the same row mapping
+        assert_eq!(db.get_all_entries().unwrap(), entries);
+        assert_eq!(db.get_entry(text.id.unwrap()).unwrap(), Some(text));
+        assert_eq!(db.get_entry(image.id.unwrap()).unwrap(), Some(image));
 
         cleanup_test_db();
     }
@@ -278,4 +958,404 @@ mod tests {
 
         cleanup_test_db();
     }
+
+    #[test]
+    fn test_list_entries_pagination() {
+        let db = create_test_db();
+
+        for i in 1..=5 {
+            db.save_entry(ClipboardEntry::new_text_entry(format!("Entry {}", i))).unwrap();
+        }
+
+        let first = db.list_entries(None, 2, &EntryFilter::default()).unwrap();
+        assert_eq!(first.entries.len(), 2);
+        assert_eq!(first.entries[0].preview, Some("Entry 5".to_string()));
+        assert_eq!(first.entries[1].preview, Some("Entry 4".to_string()));
+
+        let second = db.list_entries(first.next_cursor.as_ref(), 2, &EntryFilter::default()).unwrap();
+        assert_eq!(second.entries[0].preview, Some("Entry 3".to_string()));
+        assert_eq!(second.entries[1].preview, Some("Entry 2".to_string()));
+
+        let last = db.list_entries(second.next_cursor.as_ref(), 2, &EntryFilter::default()).unwrap();
+        assert_eq!(last.entries.len(), 1);
+        assert_eq!(last.entries[0].preview, Some("Entry 1".to_string()));
+        assert_eq!(last.next_cursor, None);
+
+        cleanup_test_db();
+    }
+
+    #[test]
+    fn test_list_entries_same_timestamp_uses_id() {
+        let db = create_test_db();
+
+        // Entries copied within the same clock tick share created_at
+        for i in 1..=3 {
+            let mut entry = ClipboardEntry::new_text_entry(format!("Entry {}", i));
+            entry.created_at = "2025-01-01 00:00:00 UTC".to_string();
+            db.save_entry(entry).unwrap();
+        }
+
+        let first = db.list_entries(None, 2, &EntryFilter::default()).unwrap();
+        let second = db.list_entries(first.next_cursor.as_ref(), 2, &EntryFilter::default()).unwrap();
+        let ids: Vec<i64> = first.entries.iter().chain(&second.entries).map(|e| e.id).collect();
+        assert_eq!(ids, vec![3, 2, 1]);
+
+        cleanup_test_db();
+    }
+
+    #[test]
+    fn test_list_entries_preview_and_filter() {
+        let db = create_test_db();
+
+        db.save_entry(ClipboardEntry::new_text_entry("x".repeat(PREVIEW_LENGTH + 50))).unwrap();
+        db.save_entry(ClipboardEntry::new_image_entry("data:image/png;base64,AAAA".to_string())).unwrap();
+
+        let filter = EntryFilter { content_type: Some(ContentType::Text), ..Default::default() };
+        let page = db.list_entries(None, 10, &filter).unwrap();
+        assert_eq!(page.entries.len(), 1);
+        assert_eq!(page.entries[0].preview.as_ref().unwrap().len(), PREVIEW_LENGTH);
+        assert!(page.entries[0].truncated);
+
+        let filter = EntryFilter { content_type: Some(ContentType::Image), ..Default::default() };
+        let page = db.list_entries(None, 10, &filter).unwrap();
+        assert_eq!(page.entries.len(), 1);
+        assert_eq!(page.entries[0].content_type, ContentType::Image);
+        assert_eq!(page.entries[0].preview, None);
+        assert!(!page.entries[0].truncated);
+        assert_eq!(page.entries[0].thumbnail, Some(format!("entries/{}/image", page.entries[0].id)));
+
+        cleanup_test_db();
+    }
+
+    #[test]
+    fn test_list_entries_clamps_limit() {
+        let db = create_test_db();
+
+        for i in 0..MAX_PAGE_SIZE + 1 {
+            db.save_entry(ClipboardEntry::new_text_entry(format!("Entry {}", i))).unwrap();
+        }
+
+        let page = db.list_entries(None, usize::MAX, &EntryFilter::default()).unwrap();
+        assert_eq!(page.entries.len(), MAX_PAGE_SIZE);
+        assert!(page.next_cursor.is_some());
+        assert_eq!(db.list_entries(None, 0, &EntryFilter::default()).unwrap().entries.len(), 1);
+
+        cleanup_test_db();
+    }
+
+    #[test]
+    fn test_summaries_texts_and_counts() {
+        let db = create_test_db();
+
+        let text_id = db.save_entry(ClipboardEntry::new_text_entry("Some text".to_string())).unwrap();
+        let image_id = db.save_entry(ClipboardEntry::new_image_entry("data:image/png;base64,AAAA".to_string())).unwrap();
+
+        let ids: Vec<i64> = db.get_summaries(&[image_id, 999, text_id]).unwrap().iter().map(|s| s.id).collect();
+        assert_eq!(ids, vec![image_id, text_id]);
+        assert_eq!(db.get_text_contents().unwrap(), vec![(text_id, "Some text".to_string())]);
+        assert_eq!(db.count_entries_by_type().unwrap(), EntryCounts { text: 1, image: 1 });
+
+        cleanup_test_db();
+    }
+
+    #[test]
+    fn test_list_entries_source_app_filter() {
+        let db = create_test_db();
+
+        let mut safari = ClipboardEntry::new_text_entry("From Safari".to_string());
+        safari.source_app_id = Some("com.apple.Safari".to_string());
+        safari.source_app_name = Some("Safari".to_string());
+        let safari_id = db.save_entry(safari).unwrap();
+        db.save_entry(ClipboardEntry::new_text_entry("Unknown source".to_string())).unwrap();
+
+        // Matches either the id or the name, ignoring case
+        for source_app in ["com.apple.safari", "SAFARI"] {
+            let filter = EntryFilter { source_app: Some(source_app.to_string()), ..Default::default() };
+            let page = db.list_entries(None, 10, &filter).unwrap();
+            assert_eq!(page.entries.len(), 1);
+            assert_eq!(page.entries[0].id, safari_id);
+            assert_eq!(page.entries[0].source_app_name, Some("Safari".to_string()));
+        }
+
+        let filter = EntryFilter { source_app: Some("Terminal".to_string()), ..Default::default() };
+        assert!(db.list_entries(None, 10, &filter).unwrap().entries.is_empty());
+
+        cleanup_test_db();
+    }
+
+    #[test]
+    fn test_get_entry() {
+        let db = create_test_db();
+
+        let id = db.save_entry(ClipboardEntry::new_text_entry("Full text".to_string())).unwrap();
+        let entry = db.get_entry(id).unwrap().unwrap();
+        assert_eq!(entry.id, Some(id));
+        assert_eq!(entry.text_content, Some("Full text".to_string()));
+        assert!(db.get_entry(id + 1).unwrap().is_none());
+
+        cleanup_test_db();
+    }
+
+    #[test]
+    fn test_save_entries_batch() {
+        let db = create_test_db();
+
+        let entries = vec![
+            ClipboardEntry::new_text_entry("First".to_string()),
+            ClipboardEntry::new_text_entry("Second".to_string()),
+        ];
+        let ids = db.save_entries(&entries).unwrap();
+        assert_eq!(ids.len(), 2);
+        assert!(ids[0] < ids[1]);
+        assert_eq!(db.get_entry(ids[1]).unwrap().unwrap().text_content, Some("Second".to_string()));
+
+        cleanup_test_db();
+    }
+
+    #[test]
+    fn test_encryption_round_trip() {
+        let db = create_test_db();
+
+        let mut before = ClipboardEntry::new_text_entry("Saved before".to_string());
+        before.source_app_name = Some("Notes".to_string());
+        before.source_window_title = Some("Passwords".to_string());
+        let plain_id = db.save_entry(before).unwrap();
+        db.enable_encryption(&KeySource::Passphrase("correct horse".to_string())).unwrap();
+        let mut after = ClipboardEntry::new_text_entry("Saved after".to_string());
+        after.source_app_name = Some("Notes".to_string());
+        let text_id = db.save_entry(after).unwrap();
+        let image_id = db.save_entry(ClipboardEntry::new_image_entry("data:image/png;base64,AAAA".to_string())).unwrap();
+
+        // Nothing is stored in plaintext, existing rows included
+        let stored: Vec<(String, bool)> = db.conn
+            .prepare("SELECT COALESCE(text_content, image_path), encrypted FROM clipboard_history").unwrap()
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?))).unwrap()
+            .collect::<Result<_>>().unwrap();
+        assert!(stored.iter().all(|(value, encrypted)| *encrypted && value.starts_with(ENCRYPTED_PREFIX)));
+        let names: Vec<String> = db.conn
+            .prepare("SELECT source_app_name FROM clipboard_history WHERE source_app_name IS NOT NULL").unwrap()
+            .query_map([], |row| row.get(0)).unwrap()
+            .collect::<Result<_>>().unwrap();
+        assert_eq!(names.len(), 2);
+        assert!(names.iter().all(|name| name.starts_with(ENCRYPTED_PREFIX)));
+
+        // Queries decrypt transparently
+        let before = db.get_entry(plain_id).unwrap().unwrap();
+        assert_eq!(before.text_content, Some("Saved before".to_string()));
+        assert_eq!(before.source_window_title, Some("Passwords".to_string()));
+        assert_eq!(db.list_entries(None, 10, &EntryFilter::default()).unwrap().entries[1].source_app_name, Some("Notes".to_string()));
+        assert_eq!(db.get_entry(text_id).unwrap().unwrap().text_content, Some("Saved after".to_string()));
+        assert_eq!(db.get_entry(image_id).unwrap().unwrap().image_path, Some("data:image/png;base64,AAAA".to_string()));
+        assert_eq!(db.get_all_entries().unwrap().len(), 3);
+
+        cleanup_test_db();
+    }
+
+    #[test]
+    fn test_plaintext_with_encrypted_prefix() {
+        let db = create_test_db();
+
+        let text = format!("{}not really ciphertext", ENCRYPTED_PREFIX);
+        let before = db.save_entry(ClipboardEntry::new_text_entry(text.clone())).unwrap();
+        assert_eq!(db.get_all_entries().unwrap()[0].text_content, Some(text.clone()));
+        assert_eq!(db.list_entries(None, 10, &EntryFilter::default()).unwrap().entries[0].preview, Some(text.clone()));
+
+        // Encrypted like any other row, and read back as written
+        db.enable_encryption(&KeySource::Passphrase("correct horse".to_string())).unwrap();
+        let after = db.save_entry(ClipboardEntry::new_text_entry(text.clone())).unwrap();
+        for id in [before, after] {
+            assert_eq!(db.get_entry(id).unwrap().unwrap().text_content, Some(text.clone()));
+        }
+        db.disable_encryption().unwrap();
+        assert_eq!(db.get_entry(before).unwrap().unwrap().text_content, Some(text));
+
+        cleanup_test_db();
+    }
+
+    #[test]
+    fn test_encrypted_preview_is_truncated() {
+        let db = create_test_db();
+
+        db.enable_encryption(&KeySource::Passphrase("correct horse".to_string())).unwrap();
+        db.save_entry(ClipboardEntry::new_text_entry("가".repeat(PREVIEW_LENGTH + 1))).unwrap();
+
+        let page = db.list_entries(None, 10, &EntryFilter::default()).unwrap();
+        assert_eq!(page.entries[0].preview, Some("가".repeat(PREVIEW_LENGTH)));
+        assert!(page.entries[0].truncated);
+
+        cleanup_test_db();
+    }
+
+    #[test]
+    fn test_locked_database() {
+        let db = create_test_db();
+
+        let id = db.save_entry(ClipboardEntry::new_text_entry("Secret".to_string())).unwrap();
+        db.enable_encryption(&KeySource::Passphrase("correct horse".to_string())).unwrap();
+        db.keys().set(None);
+
+        // Neither reads nor writes fall back to plaintext while locked
+        assert!(db.get_entry(id).is_err());
+        assert!(db.save_entry(ClipboardEntry::new_text_entry("New".to_string())).is_err());
+        assert!(db.unlock(&KeySource::Passphrase("wrong".to_string())).is_err());
+
+        db.unlock(&KeySource::Passphrase("correct horse".to_string())).unwrap();
+        assert_eq!(db.get_entry(id).unwrap().unwrap().text_content, Some("Secret".to_string()));
+        assert!(db.encryption_status().unwrap().unlocked);
+
+        cleanup_test_db();
+    }
+
+    #[test]
+    fn test_disable_encryption() {
+        let db = create_test_db();
+
+        db.enable_encryption(&KeySource::Passphrase("correct horse".to_string())).unwrap();
+        let id = db.save_entry(ClipboardEntry::new_text_entry("Secret".to_string())).unwrap();
+        db.disable_encryption().unwrap();
+
+        let stored: String = db.conn
+            .query_row("SELECT text_content FROM clipboard_history WHERE id = ?1", [id], |row| row.get(0))
+            .unwrap();
+        assert_eq!(stored, "Secret");
+        let status = db.encryption_status().unwrap();
+        assert!(!status.enabled);
+        assert!(!status.unlocked);
+
+        cleanup_test_db();
+    }
+
+    #[test]
+    fn test_delete_expired_entries() {
+        let db = create_test_db();
+
+        let mut expiring = ClipboardEntry::new_text_entry("Expiring".to_string());
+        expiring.expires_at = Some(1_000);
+        let expiring_id = db.save_entry(expiring).unwrap();
+        let kept_id = db.save_entry(ClipboardEntry::new_text_entry("Kept".to_string())).unwrap();
+
+        assert_eq!(db.get_entry(expiring_id).unwrap().unwrap().expires_at, Some(1_000));
+        assert!(db.delete_expired_entries(999).unwrap().is_empty());
+        assert_eq!(db.delete_expired_entries(1_000).unwrap(), vec![expiring_id]);
+
+        let entries = db.get_all_entries().unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].id, Some(kept_id));
+
+        cleanup_test_db();
+    }
+
+    #[test]
+    fn test_prune_history() {
+        let db = create_test_db();
+
+        let mut old = ClipboardEntry::new_text_entry("Old".to_string());
+        old.created_at = "2020-01-01 00:00:00 UTC".to_string();
+        let old_id = db.save_entry(old).unwrap();
+        let mut ids = Vec::new();
+        for i in 1..=4 {
+            ids.push(db.save_entry(ClipboardEntry::new_text_entry(format!("Entry {}", i))).unwrap());
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+
+        assert!(db.prune_history(None, None).unwrap().is_empty());
+        assert_eq!(db.prune_history(None, Some("2021-01-01 00:00:00 UTC")).unwrap(), vec![old_id]);
+
+        let mut pruned = db.prune_history(Some(2), None).unwrap();
+        pruned.sort();
+        assert_eq!(pruned, ids[..2].to_vec());
+        let remaining: Vec<_> = db.get_all_entries().unwrap().into_iter().filter_map(|e| e.id).collect();
+        assert_eq!(remaining, vec![ids[3], ids[2]]);
+
+        cleanup_test_db();
+    }
+
+    #[test]
+    fn test_get_entries_after() {
+        let db = create_test_db();
+        assert_eq!(db.latest_id().unwrap(), 0);
+
+        let first = db.save_entry(ClipboardEntry::new_text_entry("First".to_string())).unwrap();
+        let second = db.save_entry(ClipboardEntry::new_text_entry("Second".to_string())).unwrap();
+        let third = db.save_entry(ClipboardEntry::new_text_entry("Third".to_string())).unwrap();
+        assert_eq!(db.latest_id().unwrap(), third);
+        assert_eq!(db.count_entries().unwrap(), 3);
+
+        let ids: Vec<_> = db.get_entries_after(first).unwrap().into_iter().filter_map(|e| e.id).collect();
+        assert_eq!(ids, vec![second, third]);
+        assert!(db.get_entries_after(third).unwrap().is_empty());
+
+        cleanup_test_db();
+    }
+
+    #[test]
+    fn test_webhook_queue() {
+        let db = create_test_db();
+        assert_eq!(db.next_webhook_attempt().unwrap(), None);
+
+        db.queue_webhook(1, 100).unwrap();
+        db.queue_webhook(2, 100).unwrap();
+        db.queue_webhook(1, 500).unwrap();
+        assert_eq!(db.next_webhook_attempt().unwrap(), Some(100));
+        assert!(db.due_webhooks(99, 10).unwrap().is_empty());
+
+        db.retry_webhook(1, 200, "connection refused").unwrap();
+        assert_eq!(db.due_webhooks(100, 10).unwrap(), vec![QueuedWebhook { entry_id: 2, attempts: 0 }]);
+        db.remove_webhook(2).unwrap();
+        assert_eq!(db.due_webhooks(200, 10).unwrap(), vec![QueuedWebhook { entry_id: 1, attempts: 1 }]);
+
+        cleanup_test_db();
+    }
+
+    #[test]
+    fn test_pinned_entries_survive_pruning() {
+        let db = create_test_db();
+
+        let mut old = ClipboardEntry::new_text_entry("Pinned".to_string());
+        old.created_at = "2020-01-01 00:00:00 UTC".to_string();
+        let pinned_id = db.save_entry(old).unwrap();
+        let recent_id = db.save_entry(ClipboardEntry::new_text_entry("Recent".to_string())).unwrap();
+
+        assert!(db.set_pinned(pinned_id, true).unwrap());
+        assert!(!db.set_pinned(recent_id + 1, true).unwrap());
+        assert!(db.get_entry(pinned_id).unwrap().unwrap().pinned);
+
+        // Neither the age nor the count limit removes the pinned entry
+        assert!(db.prune_history(Some(1), Some("2021-01-01 00:00:00 UTC")).unwrap().is_empty());
+        let pinned: Vec<_> = db.get_pinned_summaries().unwrap().into_iter().map(|s| s.id).collect();
+        assert_eq!(pinned, vec![pinned_id]);
+
+        db.set_pinned(pinned_id, false).unwrap();
+        assert_eq!(db.prune_history(Some(1), None).unwrap(), vec![pinned_id]);
+        assert!(db.get_pinned_summaries().unwrap().is_empty());
+
+        cleanup_test_db();
+    }
+
+    #[test]
+    fn test_migrates_old_schema() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute(
+            "CREATE TABLE clipboard_history (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                content_type TEXT NOT NULL,
+                image_path TEXT,
+                text_content TEXT,
+                created_at TEXT NOT NULL
+            )",
+            [],
+        ).unwrap();
+        conn.execute(
+            "INSERT INTO clipboard_history (content_type, text_content, created_at) VALUES ('TEXT', 'Old', '2024-01-01 00:00:00 UTC')",
+            [],
+        ).unwrap();
+
+        let db = ClipboardDatabase { conn, keys: KeySlot::default() };
+        db.init_schema().unwrap();
+        let entries = db.get_all_entries().unwrap();
+        assert_eq!(entries[0].text_content, Some("Old".to_string()));
+        assert_eq!(entries[0].expires_at, None);
+
+        cleanup_test_db();
+    }
 }
@@ -0,0 +1,237 @@
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::Mutex;
+use std::thread;
+
+use crate::db::{ClipboardDatabase, ClipboardEntry};
+
+/// Number of read-only connections serving queries
+const READER_COUNT: usize = 4;
+/// Maximum number of queued inserts committed in one transaction
+const MAX_BATCH_SIZE: usize = 64;
+
+type Reply<T> = Sender<Result<T, String>>;
+type WriteJob = Box<dyn FnOnce(&ClipboardDatabase) + Send>;
+
+enum WriteRequest {
+    Save(ClipboardEntry, Reply<i64>),
+    Run(WriteJob),
+}
+
+/// Handle to the database. Writes are sent to a dedicated writer thread that owns
+/// the only read-write connection; queries run on a pool of read-only connections,
+/// so capture never waits behind a slow UI query.
+pub struct DbHandle {
+    writer: Sender<WriteRequest>,
+    readers: Vec<Mutex<ClipboardDatabase>>,
+    next_reader: AtomicUsize,
+}
+
+impl DbHandle {
+    /// Opens the database, initializes the schema and starts the writer thread
+    pub fn open(db_path: PathBuf) -> Result<Self, String> {
+        let writer_db = ClipboardDatabase::new(db_path.clone()).map_err(|e| e.to_string())?;
+        // All connections share the writer's encryption key
+        let keys = writer_db.keys().clone();
+        let readers = (0..READER_COUNT)
+            .map(|_| ClipboardDatabase::open_read_only(db_path.clone()).map(|db| Mutex::new(db.with_keys(keys.clone()))))
+            .collect::<rusqlite::Result<Vec<_>>>()
+            .map_err(|e| e.to_string())?;
+
+        let (sender, receiver) = mpsc::channel();
+        thread::Builder::new()
+            .name("db-writer".to_string())
+            .spawn(move || run_writer(writer_db, receiver))
+            .map_err(|e| e.to_string())?;
+
+        Ok(Self {
+            writer: sender,
+            readers,
+            next_reader: AtomicUsize::new(0),
+        })
+    }
+
+    /// Queues an entry for insertion and waits for its id
+    pub fn save_entry(&self, entry: ClipboardEntry) -> Result<i64, String> {
+        let (reply, response) = mpsc::channel();
+        self.send(WriteRequest::Save(entry, reply))?;
+        response.recv().map_err(|e| e.to_string())?
+    }
+
+    /// Runs a write on the writer thread and waits for its result
+    pub fn write<T, F>(&self, job: F) -> Result<T, String>
+    where
+        T: Send + 'static,
+        F: FnOnce(&ClipboardDatabase) -> rusqlite::Result<T> + Send + 'static,
+    {
+        let (reply, response) = mpsc::channel();
+        self.send(WriteRequest::Run(Box::new(move |db| {
+            let _ = reply.send(job(db).map_err(|e| e.to_string()));
+        })))?;
+        response.recv().map_err(|e| e.to_string())?
+    }
+
+    /// Runs a query on a read-only connection
+    pub fn read<T>(&self, query: impl FnOnce(&ClipboardDatabase) -> rusqlite::Result<T>) -> Result<T, String> {
+        // Prefer an idle connection, otherwise wait for one in turn
+        let db = match self.readers.iter().find_map(|reader| reader.try_lock().ok()) {
+            Some(db) => db,
+            None => {
+                let index = self.next_reader.fetch_add(1, Ordering::Relaxed) % self.readers.len();
+                self.readers[index].lock().map_err(|e| e.to_string())?
+            }
+        };
+        query(&db).map_err(|e| e.to_string())
+    }
+
+    pub fn delete_entry(&self, id: i64) -> Result<i64, String> {
+        self.write(move |db| db.delete_entry(id))
+    }
+
+    pub fn clear_all(&self) -> Result<(), String> {
+        self.write(|db| db.clear_all())
+    }
+
+    fn send(&self, request: WriteRequest) -> Result<(), String> {
+        self.writer.send(request).map_err(|_| "Database writer thread has stopped".to_string())
+    }
+}
+
+fn run_writer(db: ClipboardDatabase, receiver: Receiver<WriteRequest>) {
+    let mut pending: Option<WriteRequest> = None;
+    loop {
+        let request = match pending.take() {
+            Some(request) => request,
+            None => match receiver.recv() {
+                Ok(request) => request,
+                // Every handle has been dropped
+                Err(_) => return,
+            },
+        };
+
+        match request {
+            WriteRequest::Save(entry, reply) => {
+                // Gather inserts that queued up meanwhile into a single transaction
+                let mut batch = vec![(entry, reply)];
+                while batch.len() < MAX_BATCH_SIZE {
+                    match receiver.try_recv() {
+                        Ok(WriteRequest::Save(entry, reply)) => batch.push((entry, reply)),
+                        Ok(other) => {
+                            // Keep ordering: handle it after this batch is committed
+                            pending = Some(other);
+                            break;
+                        }
+                        Err(_) => break,
+                    }
+                }
+                save_batch(&db, batch);
+            }
+            WriteRequest::Run(job) => job(&db),
+        }
+    }
+}
+
+fn save_batch(db: &ClipboardDatabase, batch: Vec<(ClipboardEntry, Reply<i64>)>) {
+    let (entries, replies): (Vec<_>, Vec<_>) = batch.into_iter().unzip();
+    match db.save_entries(&entries) {
+        Ok(ids) => {
+            for (id, reply) in ids.into_iter().zip(replies) {
+                let _ = reply.send(Ok(id));
+            }
+        }
+        Err(e) => {
+            println!("[DB] Error saving batch of {} entries: {:?}", entries.len(), e);
+            for reply in replies {
+                let _ = reply.send(Err(e.to_string()));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+    use std::sync::Arc;
+
+    fn create_test_handle(name: &str) -> (DbHandle, PathBuf) {
+        // Read-only connections need a file to share with the writer
+        let db_path = std::env::temp_dir().join(format!("clipboard_actor_{}_{}.db", name, std::process::id()));
+        cleanup_test_db(&db_path);
+        (DbHandle::open(db_path.clone()).unwrap(), db_path)
+    }
+
+    fn cleanup_test_db(db_path: &Path) {
+        for suffix in ["", "-wal", "-shm"] {
+            let _ = std::fs::remove_file(format!("{}{}", db_path.display(), suffix));
+        }
+    }
+
+    #[test]
+    fn test_save_and_read() {
+        let (handle, db_path) = create_test_handle("save_and_read");
+
+        let id = handle.save_entry(ClipboardEntry::new_text_entry("Hello".to_string())).unwrap();
+        let entry = handle.read(|db| db.get_entry(id)).unwrap().unwrap();
+        assert_eq!(entry.text_content, Some("Hello".to_string()));
+
+        drop(handle);
+        cleanup_test_db(&db_path);
+    }
+
+    #[test]
+    fn test_writes_keep_order() {
+        let (handle, db_path) = create_test_handle("writes_keep_order");
+
+        let id = handle.save_entry(ClipboardEntry::new_text_entry("First".to_string())).unwrap();
+        handle.delete_entry(id).unwrap();
+        handle.save_entry(ClipboardEntry::new_text_entry("Second".to_string())).unwrap();
+
+        let entries = handle.read(|db| db.get_all_entries()).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].text_content, Some("Second".to_string()));
+
+        handle.clear_all().unwrap();
+        assert!(handle.read(|db| db.get_all_entries()).unwrap().is_empty());
+
+        drop(handle);
+        cleanup_test_db(&db_path);
+    }
+
+    #[test]
+    fn test_concurrent_saves() {
+        let (handle, db_path) = create_test_handle("concurrent_saves");
+        let handle = Arc::new(handle);
+
+        let workers: Vec<_> = (0..8)
+            .map(|i| {
+                let handle = handle.clone();
+                thread::spawn(move || {
+                    for j in 0..10 {
+                        let text = format!("Entry {}-{}", i, j);
+                        handle.save_entry(ClipboardEntry::new_text_entry(text)).unwrap();
+                    }
+                })
+            })
+            .collect();
+        for worker in workers {
+            worker.join().unwrap();
+        }
+
+        assert_eq!(handle.read(|db| db.get_all_entries()).unwrap().len(), 80);
+
+        drop(handle);
+        cleanup_test_db(&db_path);
+    }
+
+    #[test]
+    fn test_readers_cannot_write() {
+        let (handle, db_path) = create_test_handle("readers_cannot_write");
+
+        assert!(handle.read(|db| db.clear_all()).is_err());
+
+        drop(handle);
+        cleanup_test_db(&db_path);
+    }
+}
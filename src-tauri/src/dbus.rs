@@ -0,0 +1,281 @@
+use std::sync::mpsc::{self, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use zbus::blocking::{connection, Connection};
+use zbus::fdo;
+use zbus::interface;
+use zbus::object_server::SignalEmitter;
+
+use crate::capture::{Capture, CaptureEvent};
+use crate::db::{ClipboardEntry, ContentType, EntryFilter, EntrySummary, PREVIEW_LENGTH};
+use crate::ipc::{self, Request};
+use crate::search::SearchEngine;
+
+pub const BUS_NAME: &str = "com.hyukmac.ClipboardWatcher";
+pub const OBJECT_PATH: &str = "/com/hyukmac/ClipboardWatcher";
+pub const INTERFACE_NAME: &str = "com.hyukmac.ClipboardWatcher1";
+
+/// (id, content type, created at, preview) of an entry, as listed over D-Bus.
+/// Previews are cut to PREVIEW_LENGTH characters; GetText returns the full text.
+type EntryRow = (i64, String, String, String);
+
+fn content_type_name(content_type: &ContentType) -> String {
+    content_type.as_db_str().to_lowercase()
+}
+
+fn summary_row(entry: EntrySummary) -> EntryRow {
+    let preview = entry.preview.unwrap_or_else(|| "[image]".to_string());
+    (entry.id, content_type_name(&entry.content_type), entry.created_at, preview)
+}
+
+fn entry_row(entry: &ClipboardEntry) -> EntryRow {
+    let preview = match entry.content_type {
+        ContentType::Text => entry.text_content.as_deref().unwrap_or_default().chars().take(PREVIEW_LENGTH).collect(),
+        ContentType::Image => "[image]".to_string(),
+    };
+    (entry.id.unwrap_or_default(), content_type_name(&entry.content_type), entry.created_at.clone(), preview)
+}
+
+fn failed(message: String) -> fdo::Error {
+    fdo::Error::Failed(message)
+}
+
+/// Object exported on the session bus for desktop integrations and `busctl`
+pub struct ClipboardService {
+    capture: Arc<Capture>,
+    search: Arc<SearchEngine>,
+}
+
+#[interface(name = "com.hyukmac.ClipboardWatcher1")]
+impl ClipboardService {
+    /// Most recent entries, newest first
+    fn list(&self, limit: u32) -> fdo::Result<Vec<EntryRow>> {
+        let filter = EntryFilter::default();
+        let page = self.capture.db.read(|db| db.list_entries(None, limit as usize, &filter)).map_err(failed)?;
+        Ok(page.entries.into_iter().map(summary_row).collect())
+    }
+
+    /// Best matches for the query, best first
+    fn search(&self, query: &str, limit: u32) -> fdo::Result<Vec<EntryRow>> {
+        let entries = ipc::search_entries(&self.capture, &self.search, query, limit as usize).map_err(failed)?;
+        Ok(entries.iter().map(entry_row).collect())
+    }
+
+    /// Full text of an entry, empty for images
+    fn get_text(&self, id: i64) -> fdo::Result<String> {
+        let entry = ipc::get_entry(&self.capture, id).map_err(failed)?;
+        Ok(entry.text_content.unwrap_or_default())
+    }
+
+    /// Puts an entry back on the clipboard
+    fn copy(&self, id: i64) -> fdo::Result<()> {
+        self.run(Request::Copy { id })
+    }
+
+    fn delete(&self, id: i64) -> fdo::Result<()> {
+        self.run(Request::Delete { id })
+    }
+
+    fn set_pinned(&self, id: i64, pinned: bool) -> fdo::Result<()> {
+        self.run(Request::Pin { id, pinned })
+    }
+
+    /// Pauses capture for the given number of minutes, or indefinitely for 0
+    fn pause(&self, minutes: u32) -> fdo::Result<()> {
+        let minutes = (minutes > 0).then_some(minutes as u64);
+        self.run(Request::Pause { minutes })
+    }
+
+    fn resume(&self) -> fdo::Result<()> {
+        self.run(Request::Resume)
+    }
+
+    #[zbus(property)]
+    fn paused(&self) -> bool {
+        self.capture.pause.is_paused(chrono::Utc::now())
+    }
+
+    /// Number of entries in the history
+    #[zbus(property)]
+    fn count(&self) -> fdo::Result<i64> {
+        self.capture.db.read(|db| db.count_entries()).map_err(failed)
+    }
+
+    #[zbus(signal)]
+    async fn clipboard_changed(emitter: &SignalEmitter<'_>, id: i64, content_type: &str, preview: &str) -> zbus::Result<()>;
+
+    #[zbus(signal)]
+    async fn clipboard_deleted(emitter: &SignalEmitter<'_>, id: i64) -> zbus::Result<()>;
+
+    #[zbus(signal)]
+    async fn clipboard_pinned(emitter: &SignalEmitter<'_>, id: i64, pinned: bool) -> zbus::Result<()>;
+}
+
+impl ClipboardService {
+    fn run(&self, request: Request) -> fdo::Result<()> {
+        ipc::handle(&self.capture, &self.search, request).map(|_| ()).map_err(failed)
+    }
+}
+
+/// Connects to the bus, exports the service and claims its name
+pub fn serve(builder: connection::Builder<'_>, capture: Arc<Capture>, search: Arc<SearchEngine>) -> Result<Connection, String> {
+    builder
+        .name(BUS_NAME)
+        .and_then(|builder| builder.serve_at(OBJECT_PATH, ClipboardService { capture, search }))
+        .and_then(|builder| builder.build())
+        .map_err(|e| e.to_string())
+}
+
+// Mirrors a capture event as signals, like the events the app windows receive
+fn emit_signals(connection: &Connection, event: &CaptureEvent) -> zbus::Result<()> {
+    let service = connection.object_server().interface::<_, ClipboardService>(OBJECT_PATH)?;
+    let emitter = service.signal_emitter();
+    zbus::block_on(async {
+        match event {
+            CaptureEvent::EntryAdded(entry) => {
+                let (id, content_type, _, preview) = entry_row(entry);
+                ClipboardService::clipboard_changed(emitter, id, &content_type, &preview).await?;
+                service.get().count_changed(emitter).await
+            }
+            CaptureEvent::EntryDeleted(id) => {
+                ClipboardService::clipboard_deleted(emitter, *id).await?;
+                service.get().count_changed(emitter).await
+            }
+            CaptureEvent::EntryPinned { id, pinned } => ClipboardService::clipboard_pinned(emitter, *id, *pinned).await,
+            CaptureEvent::PauseChanged(_) => service.get().paused_changed(emitter).await,
+            CaptureEvent::SettingsChanged(_) | CaptureEvent::CaptureStarted => Ok(()),
+        }
+    })
+}
+
+// Sends capture events as signals of the service exported on the connection. They go out
+// from a thread of their own, since emitting waits on the bus and events are also reported
+// while a D-Bus method call is being handled.
+fn forward_signals(connection: Connection) -> Sender<CaptureEvent> {
+    let (sender, receiver) = mpsc::channel::<CaptureEvent>();
+    thread::spawn(move || {
+        for event in receiver {
+            if let Err(e) = emit_signals(&connection, &event) {
+                println!("[DBUS] Failed to emit signal: {}", e);
+            }
+        }
+    });
+    sender
+}
+
+/// Exports the service on the session bus whenever this process is capturing
+pub fn attach(capture: Arc<Capture>, search: Arc<SearchEngine>) {
+    let signals: Mutex<Option<Sender<CaptureEvent>>> = Mutex::new(None);
+
+    let subscribed = Arc::clone(&capture);
+    capture.subscribe(move |event| {
+        let mut signals = signals.lock().unwrap();
+        if let CaptureEvent::CaptureStarted = event {
+            let builder = match connection::Builder::session() {
+                Ok(builder) => builder,
+                Err(e) => return println!("[DBUS] No session bus: {}", e),
+            };
+            match serve(builder, Arc::clone(&subscribed), Arc::clone(&search)) {
+                Ok(bus) => {
+                    println!("[DBUS] Serving {} on the session bus", BUS_NAME);
+                    *signals = Some(forward_signals(bus));
+                }
+                Err(e) => println!("[DBUS] Failed to export service: {}", e),
+            }
+        } else if let Some(sender) = signals.as_ref() {
+            let _ = sender.send(event.clone());
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::capture::TestCapture;
+    use std::io::{BufRead, BufReader};
+    use std::process::{Child, Command, Stdio};
+    use zbus::blocking::MessageIterator;
+    use zbus::MatchRule;
+
+    // Private bus, stopped when dropped
+    struct TestBus {
+        daemon: Child,
+        address: String,
+    }
+
+    impl TestBus {
+        fn start() -> Option<Self> {
+            let mut daemon = Command::new("dbus-daemon")
+                .args(["--session", "--nofork", "--print-address"])
+                .stdout(Stdio::piped())
+                .spawn()
+                .ok()?;
+            let mut address = String::new();
+            BufReader::new(daemon.stdout.take()?).read_line(&mut address).ok()?;
+            Some(Self { daemon, address: address.trim().to_string() })
+        }
+
+        fn connect(&self) -> connection::Builder<'_> {
+            connection::Builder::address(self.address.as_str()).unwrap()
+        }
+    }
+
+    impl Drop for TestBus {
+        fn drop(&mut self) {
+            let _ = self.daemon.kill();
+            let _ = self.daemon.wait();
+        }
+    }
+
+    #[test]
+    fn test_entry_row_preview_is_truncated() {
+        let mut entry = ClipboardEntry::new_text_entry("x".repeat(PREVIEW_LENGTH * 2));
+        entry.id = Some(4);
+        let (id, content_type, _, preview) = entry_row(&entry);
+        assert_eq!((id, content_type.as_str()), (4, "text"));
+        assert_eq!(preview, "x".repeat(PREVIEW_LENGTH));
+    }
+
+    #[test]
+    fn test_methods_and_signals() {
+        let Some(bus) = TestBus::start() else {
+            println!("dbus-daemon is not installed, skipping");
+            return;
+        };
+        let capture = TestCapture::new("dbus_service");
+        let id = capture.db.save_entry(ClipboardEntry::new_text_entry("hello".to_string())).unwrap();
+        let service = serve(bus.connect(), Arc::clone(&capture), Arc::new(SearchEngine::new())).unwrap();
+        let signals = forward_signals(service);
+        capture.subscribe(move |event| signals.send(event.clone()).unwrap());
+
+        let client = bus.connect().build().unwrap();
+
+        let reply = client.call_method(Some(BUS_NAME), OBJECT_PATH, Some(INTERFACE_NAME), "List", &(10u32,)).unwrap();
+        let rows: Vec<EntryRow> = reply.body().deserialize().unwrap();
+        assert_eq!(rows, vec![(id, "text".to_string(), rows[0].2.clone(), "hello".to_string())]);
+
+        let reply = client.call_method(Some(BUS_NAME), OBJECT_PATH, Some(INTERFACE_NAME), "GetText", &(id,)).unwrap();
+        assert_eq!(reply.body().deserialize::<String>().unwrap(), "hello");
+        assert!(client.call_method(Some(BUS_NAME), OBJECT_PATH, Some(INTERFACE_NAME), "GetText", &(999i64,)).is_err());
+
+        let rule = MatchRule::builder()
+            .msg_type(zbus::message::Type::Signal)
+            .interface(INTERFACE_NAME)
+            .unwrap()
+            .member("ClipboardDeleted")
+            .unwrap()
+            .build();
+        let mut signals = MessageIterator::for_match_rule(rule, &client, None).unwrap();
+
+        client.call_method(Some(BUS_NAME), OBJECT_PATH, Some(INTERFACE_NAME), "Delete", &(id,)).unwrap();
+        let signal = signals.next().unwrap().unwrap();
+        assert_eq!(signal.body().deserialize::<i64>().unwrap(), id);
+
+        let reply = client
+            .call_method(Some(BUS_NAME), OBJECT_PATH, Some("org.freedesktop.DBus.Properties"), "Get", &(INTERFACE_NAME, "Count"))
+            .unwrap();
+        let count = reply.body().deserialize::<zbus::zvariant::OwnedValue>().unwrap();
+        assert_eq!(i64::try_from(count).unwrap(), 0);
+    }
+}
@@ -8,17 +8,16 @@ const GAP_SCORE: i32 = -2;
 
 const NO_SCORE: i32 = -10000;
 
-// Calculate the bonus score for the text
-fn calculate_bonus_score(text: &String) -> Vec<i32> {
-    let mut score = vec![0; text.len()];
-    let length = text.len();
+// Calculate the bonus score for each char of the text
+fn calculate_bonus_score(chars: &[char]) -> Vec<i32> {
+    let mut score = vec![0; chars.len()];
 
-    for (i, char) in text.chars().enumerate() {
+    for (i, &char) in chars.iter().enumerate() {
         if i == 0 {
             score[i] = INITIAL_SCORE;
         }
         else {
-            let prev_char = text.chars().nth(i - 1).unwrap();
+            let prev_char = chars[i - 1];
             if matches!(prev_char, '/' | '_' | '-' | '.' | ' ') {
                 score[i] = BOUNDARY_SCORE;
             }
@@ -31,17 +30,18 @@ fn calculate_bonus_score(text: &String) -> Vec<i32> {
     score
 }
 
-pub fn calculate_fzf_score(text: &String, query: &String) -> Vec<i32> {
-    let bonus_score = calculate_bonus_score(text);
+pub fn calculate_fzf_score(text: &str, query: &str) -> Vec<i32> {
+    let chars: Vec<char> = text.chars().collect();
+    let bonus_score = calculate_bonus_score(&chars);
 
     // 이전 행의 점수 저장
-    let mut prev_score: Vec<i32> = vec![NO_SCORE; text.len()];
+    let mut prev_score: Vec<i32> = vec![NO_SCORE; chars.len()];
 
     for (i, q_char) in query.chars().enumerate() {
         // 현재 행의 점수 저장
-        let mut current_score = vec![NO_SCORE; text.len()];
+        let mut current_score = vec![NO_SCORE; chars.len()];
         let mut current_best_score = NO_SCORE;
-        for (j, t_char) in text.chars().enumerate() {
+        for (j, t_char) in chars.iter().enumerate() {
             // 첫 행이 아닌 경우
             if i > 0 {
                 // 이전 최고 점수가 있는 경우
@@ -60,7 +60,7 @@ pub fn calculate_fzf_score(text: &String, query: &String) -> Vec<i32> {
                 current_best_score = 0;
             }
 
-            if q_char.eq_ignore_ascii_case(&t_char) {
+            if q_char.eq_ignore_ascii_case(t_char) {
                 // 현재 행에 대한 점수 = 이전 행의 최고 점수 + 보너스 점수 + 매칭 점수
                 current_score[j] = current_best_score + bonus_score[j] + MATCH_SCORE;
             }
@@ -70,6 +70,21 @@ pub fn calculate_fzf_score(text: &String, query: &String) -> Vec<i32> {
     prev_score
 }
 
+// Check that every query char appears in the text in order
+fn is_subsequence(text: &str, query: &str) -> bool {
+    let mut text_chars = text.chars();
+    query.chars().all(|q_char| text_chars.any(|t_char| q_char.eq_ignore_ascii_case(&t_char)))
+}
+
+// Best score of the query against the text, or None if the query doesn't match.
+// Callers pass both sides already in NFC, see SearchEngine.
+pub fn calculate_match_score(text: &str, query: &str) -> Option<i32> {
+    if !is_subsequence(text, query) {
+        return None;
+    }
+    calculate_fzf_score(text, query).into_iter().max()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -175,4 +190,14 @@ mod tests {
         assert!(max_score1 > max_score2,
             "Match at initial position should score higher");
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_match_score() {
+        let text = String::from("hello_world");
+
+        assert!(calculate_match_score(&text, &String::from("hw")).is_some());
+        assert!(calculate_match_score(&text, &String::from("HW")).is_some());
+        assert_eq!(calculate_match_score(&text, &String::from("wh")), None);
+        assert_eq!(calculate_match_score(&text, &String::from("xyz")), None);
+    }
+}
@@ -0,0 +1,271 @@
+use std::io::{Read, Write};
+use std::os::unix::process::CommandExt;
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::sync::mpsc;
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::capture::{Capture, CaptureEvent};
+use crate::db::{ClipboardEntry, ContentType};
+use crate::patterns::Patterns;
+
+const DEFAULT_TIMEOUT_SECS: u64 = 10;
+// How often a running hook is checked for having exited
+const WAIT_INTERVAL: Duration = Duration::from_millis(20);
+// Longest hook output written to the log
+const MAX_LOGGED_OUTPUT: usize = 1000;
+
+/// Command run when a copy matches, e.g. opening a ticket for a copied issue key
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Hook {
+    pub name: String,
+    /// Regex the copied text must contain; None matches any entry
+    #[serde(default)]
+    pub pattern: Option<String>,
+    /// Only entries of this type; None matches text and images
+    #[serde(default)]
+    pub content_type: Option<ContentType>,
+    /// Run with `sh -c`, receiving the content on stdin
+    pub command: String,
+    /// The command is killed if it runs longer than this
+    #[serde(default = "default_timeout_secs")]
+    pub timeout_secs: u64,
+}
+
+fn default_timeout_secs() -> u64 {
+    DEFAULT_TIMEOUT_SECS
+}
+
+impl Hook {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.command.trim().is_empty() {
+            return Err(format!("Hook {:?} has no command", self.name));
+        }
+        if self.timeout_secs == 0 {
+            return Err(format!("Hook {:?} needs a timeout of at least 1 second", self.name));
+        }
+        if let Some(pattern) = &self.pattern {
+            Regex::new(pattern).map_err(|e| format!("Invalid pattern for hook {:?}: {}", self.name, e))?;
+        }
+        Ok(())
+    }
+
+    /// Whether the hook runs for the entry, with the text its pattern matched
+    pub fn matches(&self, entry: &ClipboardEntry, patterns: &Patterns) -> Option<Option<String>> {
+        if self.content_type.as_ref().is_some_and(|content_type| *content_type != entry.content_type) {
+            return None;
+        }
+        let Some(pattern) = &self.pattern else {
+            return Some(None);
+        };
+        let found = patterns.get(pattern)?.find(entry.text_content.as_deref()?)?;
+        Some(Some(found.as_str().to_string()))
+    }
+}
+
+/// How a hook run ended
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HookOutcome {
+    /// None when the hook was killed
+    pub status: Option<i32>,
+    pub timed_out: bool,
+    pub stdout: String,
+    pub stderr: String,
+}
+
+// What the hook reads on stdin: the text, or the PNG bytes of an image
+fn hook_input(entry: &ClipboardEntry) -> Vec<u8> {
+    match entry.content_type {
+        ContentType::Text => entry.text_content.clone().unwrap_or_default().into_bytes(),
+        ContentType::Image => entry.image_bytes().unwrap_or_default(),
+    }
+}
+
+// Collects a pipe's output on its own thread, so a chatty hook never blocks on a full pipe
+fn read_pipe(pipe: Option<impl Read + Send + 'static>) -> thread::JoinHandle<String> {
+    thread::spawn(move || {
+        let mut output = Vec::new();
+        if let Some(mut pipe) = pipe {
+            let _ = pipe.read_to_end(&mut output);
+        }
+        String::from_utf8_lossy(&output).into_owned()
+    })
+}
+
+fn wait_with_timeout(child: &mut Child, timeout: Duration) -> Result<Option<ExitStatus>, String> {
+    let deadline = Instant::now() + timeout;
+    loop {
+        if let Some(status) = child.try_wait().map_err(|e| e.to_string())? {
+            return Ok(Some(status));
+        }
+        if Instant::now() >= deadline {
+            // Also stops anything the shell started, which would keep the output pipes open
+            // SAFETY: killpg only sends a signal to the hook's own process group
+            unsafe { libc::killpg(child.id() as libc::pid_t, libc::SIGKILL) };
+            let _ = child.wait();
+            return Ok(None);
+        }
+        thread::sleep(WAIT_INTERVAL);
+    }
+}
+
+/// Runs the hook for an entry, passing its content on stdin and its metadata in
+/// CLIPBOARDWATCHER_* environment variables
+pub fn run_hook(hook: &Hook, entry: &ClipboardEntry, matched: Option<&str>) -> Result<HookOutcome, String> {
+    let mut command = Command::new("sh");
+    command
+        .arg("-c")
+        .arg(&hook.command)
+        .env("CLIPBOARDWATCHER_HOOK", &hook.name)
+        .env("CLIPBOARDWATCHER_ID", entry.id.map(|id| id.to_string()).unwrap_or_default())
+        .env("CLIPBOARDWATCHER_CONTENT_TYPE", entry.content_type.as_db_str().to_lowercase())
+        .env("CLIPBOARDWATCHER_CREATED_AT", &entry.created_at)
+        .env("CLIPBOARDWATCHER_SOURCE_APP", entry.source_app_id.as_deref().unwrap_or_default())
+        .env("CLIPBOARDWATCHER_SOURCE_APP_NAME", entry.source_app_name.as_deref().unwrap_or_default())
+        .env("CLIPBOARDWATCHER_MATCH", matched.unwrap_or_default())
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .process_group(0);
+    let mut child = command.spawn().map_err(|e| e.to_string())?;
+
+    let input = hook_input(entry);
+    let stdin = child.stdin.take();
+    // Hooks that ignore stdin close it early, which is not an error
+    let writer = thread::spawn(move || {
+        if let Some(mut stdin) = stdin {
+            let _ = stdin.write_all(&input);
+        }
+    });
+    let stdout = read_pipe(child.stdout.take());
+    let stderr = read_pipe(child.stderr.take());
+
+    let status = wait_with_timeout(&mut child, Duration::from_secs(hook.timeout_secs))?;
+    let _ = writer.join();
+    Ok(HookOutcome {
+        status: status.and_then(|status| status.code()),
+        timed_out: status.is_none(),
+        stdout: stdout.join().unwrap_or_default(),
+        stderr: stderr.join().unwrap_or_default(),
+    })
+}
+
+fn log_output(name: &str, output: &str) {
+    let output = output.trim();
+    if output.is_empty() {
+        return;
+    }
+    let shown: String = output.chars().take(MAX_LOGGED_OUTPUT).collect();
+    println!("[HOOK] {}: {}", name, shown);
+}
+
+/// Runs the configured hooks for entries this process captures, one at a time
+/// on a worker thread
+pub fn attach(capture: Arc<Capture>) {
+    let (sender, receiver) = mpsc::channel::<(Hook, ClipboardEntry, Option<String>)>();
+    thread::spawn(move || {
+        for (hook, entry, matched) in receiver {
+            println!("[HOOK] Running {:?} for entry {:?}", hook.name, entry.id);
+            match run_hook(&hook, &entry, matched.as_deref()) {
+                Ok(outcome) if outcome.timed_out => {
+                    println!("[HOOK] {:?} timed out after {} s and was killed", hook.name, hook.timeout_secs)
+                }
+                Ok(outcome) => {
+                    println!("[HOOK] {:?} exited with status {:?}", hook.name, outcome.status);
+                    log_output(&hook.name, &outcome.stdout);
+                    log_output(&hook.name, &outcome.stderr);
+                }
+                Err(e) => println!("[HOOK] Failed to run {:?}: {}", hook.name, e),
+            }
+        }
+    });
+
+    let subscribed = Arc::clone(&capture);
+    capture.subscribe(move |event| {
+        // A process following another's capture would run every hook twice
+        let CaptureEvent::EntryAdded(entry) = event else {
+            return;
+        };
+        if !subscribed.is_capturing() {
+            return;
+        }
+        let hooks = subscribed.settings.read().unwrap().hooks.clone();
+        let patterns = subscribed.patterns.read().unwrap();
+        for hook in hooks {
+            if let Some(matched) = hook.matches(entry, &patterns) {
+                let _ = sender.send((hook, entry.clone(), matched));
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hook(pattern: Option<&str>, command: &str) -> Hook {
+        Hook {
+            name: "test".to_string(),
+            pattern: pattern.map(String::from),
+            content_type: None,
+            command: command.to_string(),
+            timeout_secs: 5,
+        }
+    }
+
+    #[test]
+    fn test_matches() {
+        let entry = ClipboardEntry::new_text_entry("see PROJ-123 for details".to_string());
+        let image = ClipboardEntry::new_image_entry("data:image/png;base64,AAAA".to_string());
+
+        let jira = hook(Some(r"\b[A-Z]+-\d+\b"), "true");
+        let patterns = Patterns::compile(jira.pattern.as_deref()).unwrap();
+        assert_eq!(jira.matches(&entry, &patterns), Some(Some("PROJ-123".to_string())));
+        assert_eq!(jira.matches(&ClipboardEntry::new_text_entry("nothing here".to_string()), &patterns), None);
+        assert_eq!(jira.matches(&image, &patterns), None);
+        // A pattern that isn't in the applied settings never matches
+        assert_eq!(jira.matches(&entry, &Patterns::default()), None);
+
+        let images = Hook { content_type: Some(ContentType::Image), ..hook(None, "true") };
+        assert_eq!(images.matches(&image, &patterns), Some(None));
+        assert_eq!(images.matches(&entry, &patterns), None);
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(hook(Some("[a-z]+"), "true").validate().is_ok());
+        assert!(hook(Some("("), "true").validate().is_err());
+        assert!(hook(None, " ").validate().is_err());
+        assert!(Hook { timeout_secs: 0, ..hook(None, "true") }.validate().is_err());
+    }
+
+    #[test]
+    fn test_run_passes_content_and_metadata() {
+        let mut entry = ClipboardEntry::new_text_entry("see PROJ-123".to_string());
+        entry.id = Some(7);
+        entry.source_app_name = Some("Terminal".to_string());
+        let command = r#"printf '%s %s %s %s|' "$CLIPBOARDWATCHER_ID" "$CLIPBOARDWATCHER_CONTENT_TYPE" "$CLIPBOARDWATCHER_SOURCE_APP_NAME" "$CLIPBOARDWATCHER_MATCH"; cat; echo oops >&2; exit 3"#;
+
+        let outcome = run_hook(&hook(None, command), &entry, Some("PROJ-123")).unwrap();
+        assert_eq!(outcome.stdout, "7 text Terminal PROJ-123|see PROJ-123");
+        assert_eq!(outcome.stderr, "oops\n");
+        assert_eq!(outcome.status, Some(3));
+        assert!(!outcome.timed_out);
+    }
+
+    #[test]
+    fn test_slow_hook_is_killed() {
+        let entry = ClipboardEntry::new_text_entry("text".to_string());
+        let slow = Hook { timeout_secs: 1, ..hook(None, "sleep 30") };
+
+        let started = Instant::now();
+        let outcome = run_hook(&slow, &entry, None).unwrap();
+        assert!(outcome.timed_out);
+        assert_eq!(outcome.status, None);
+        assert!(started.elapsed() < Duration::from_secs(10));
+    }
+}
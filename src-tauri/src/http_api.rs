@@ -0,0 +1,583 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{Ipv4Addr, TcpListener, TcpStream};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use crate::base::set_clipboard_entry;
+use crate::capture::{Capture, CaptureEvent};
+use crate::crypto::constant_time_eq;
+use crate::db::{ClipboardEntry, ContentType, EntryCursor, EntryFilter};
+use crate::ipc::{event_message, search_entries};
+use crate::search::SearchEngine;
+
+const DEFAULT_PORT: u16 = 47_800;
+const DEFAULT_LIMIT: usize = 50;
+/// Longest request line and headers accepted, requests have no body
+const MAX_REQUEST_SIZE: u64 = 16 * 1024;
+/// Comment sent on idle event streams, so closed connections are noticed
+const KEEPALIVE_INTERVAL: Duration = Duration::from_secs(30);
+/// How long a client may take to send its request
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// HTTP API for tools that can't use the control socket, off by default
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HttpApiSettings {
+    pub enabled: bool,
+    /// Port on 127.0.0.1; changes take effect when the app restarts
+    pub port: u16,
+    /// Bearer token every request must carry
+    pub token: Option<String>,
+}
+
+impl Default for HttpApiSettings {
+    fn default() -> Self {
+        Self { enabled: false, port: DEFAULT_PORT, token: None }
+    }
+}
+
+// Settings are logged, the token must not be
+impl fmt::Debug for HttpApiSettings {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HttpApiSettings")
+            .field("enabled", &self.enabled)
+            .field("port", &self.port)
+            .field("token", &self.token.as_ref().map(|_| "<redacted>"))
+            .finish()
+    }
+}
+
+impl HttpApiSettings {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.port == 0 {
+            return Err("HTTP API port must not be 0".to_string());
+        }
+        if self.enabled && self.token.as_deref().is_none_or(str::is_empty) {
+            return Err("Generate a token before enabling the HTTP API".to_string());
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Route {
+    /// GET /entries?limit=&type=&app=&cursor=<id>:<created_at>
+    ListEntries,
+    /// GET /entries/<id>
+    GetEntry(i64),
+    /// GET /entries/<id>/image, the PNG bytes
+    EntryImage(i64),
+    /// POST /entries/<id>/copy
+    CopyEntry(i64),
+    /// GET /search?q=&limit=
+    Search,
+    /// GET /events, a Server-Sent Events stream of history changes
+    Events,
+}
+
+/// Route for a request, None when nothing matches
+pub fn route(method: &str, path: &str) -> Option<Route> {
+    let segments: Vec<&str> = path.trim_matches('/').split('/').collect();
+    let id = |segment: &str| segment.parse::<i64>().ok();
+    match (method, segments.as_slice()) {
+        ("GET", ["entries"]) => Some(Route::ListEntries),
+        ("GET", ["entries", entry_id]) => id(entry_id).map(Route::GetEntry),
+        ("GET", ["entries", entry_id, "image"]) => id(entry_id).map(Route::EntryImage),
+        ("POST", ["entries", entry_id, "copy"]) => id(entry_id).map(Route::CopyEntry),
+        ("GET", ["search"]) => Some(Route::Search),
+        ("GET", ["events"]) => Some(Route::Events),
+        _ => None,
+    }
+}
+
+/// Decoded `key=value` pairs of a query string
+pub fn parse_query(query: &str) -> HashMap<String, String> {
+    query
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+            (percent_decode(key), percent_decode(value))
+        })
+        .collect()
+}
+
+fn percent_decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let hex = |byte: u8| (byte as char).to_digit(16);
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'+' => decoded.push(b' '),
+            b'%' => match (bytes.get(i + 1).and_then(|&b| hex(b)), bytes.get(i + 2).and_then(|&b| hex(b))) {
+                (Some(high), Some(low)) => {
+                    decoded.push((high * 16 + low) as u8);
+                    i += 2;
+                }
+                // Not an escape, keep it as written
+                _ => decoded.push(b'%'),
+            },
+            byte => decoded.push(byte),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// Cursor written as `<id>:<created_at>`, the fields of the page's next_cursor
+pub fn parse_cursor(value: &str) -> Result<EntryCursor, String> {
+    let (id, created_at) = value.split_once(':').ok_or("Cursor must be <id>:<created_at>".to_string())?;
+    let id = id.parse().map_err(|_| format!("Invalid cursor id {:?}", id))?;
+    Ok(EntryCursor { created_at: created_at.to_string(), id })
+}
+
+fn query_limit(query: &HashMap<String, String>) -> Result<usize, HttpError> {
+    match query.get("limit") {
+        Some(limit) => limit.parse::<usize>().map_err(|_| HttpError::bad_request("limit must be a number")),
+        None => Ok(DEFAULT_LIMIT),
+    }
+}
+
+struct Request {
+    method: String,
+    path: String,
+    query: HashMap<String, String>,
+    headers: HashMap<String, String>,
+}
+
+impl Request {
+    fn read(stream: &TcpStream) -> Result<Self, HttpError> {
+        // Otherwise a client that never finishes its request holds a thread forever
+        stream.set_read_timeout(Some(REQUEST_TIMEOUT)).map_err(|e| e.to_string())?;
+        let mut reader = BufReader::new(stream.take(MAX_REQUEST_SIZE));
+        let mut line = String::new();
+        reader.read_line(&mut line).map_err(|_| HttpError::bad_request("Unreadable request"))?;
+        let mut parts = line.split_whitespace();
+        let (Some(method), Some(target)) = (parts.next(), parts.next()) else {
+            return Err(HttpError::bad_request("Malformed request line"));
+        };
+        let (path, query) = target.split_once('?').unwrap_or((target, ""));
+        let mut request = Self {
+            method: method.to_string(),
+            path: path.to_string(),
+            query: parse_query(query),
+            headers: HashMap::new(),
+        };
+
+        loop {
+            line.clear();
+            if reader.read_line(&mut line).map_err(|_| HttpError::bad_request("Unreadable request"))? == 0 {
+                return Err(HttpError::bad_request("Request headers are incomplete or too large"));
+            }
+            let header = line.trim_end();
+            if header.is_empty() {
+                return Ok(request);
+            }
+            if let Some((name, value)) = header.split_once(':') {
+                request.headers.insert(name.trim().to_lowercase(), value.trim().to_string());
+            }
+        }
+    }
+
+    fn bearer_token(&self) -> Option<&str> {
+        self.headers.get("authorization")?.strip_prefix("Bearer ").map(str::trim)
+    }
+}
+
+#[derive(Debug)]
+struct HttpError {
+    status: u16,
+    message: String,
+}
+
+impl HttpError {
+    fn new(status: u16, message: impl Into<String>) -> Self {
+        Self { status, message: message.into() }
+    }
+
+    fn bad_request(message: impl Into<String>) -> Self {
+        Self::new(400, message)
+    }
+
+    fn not_found(message: impl Into<String>) -> Self {
+        Self::new(404, message)
+    }
+}
+
+// Database and clipboard failures are the server's fault
+impl From<String> for HttpError {
+    fn from(message: String) -> Self {
+        Self::new(500, message)
+    }
+}
+
+fn status_text(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        204 => "No Content",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        404 => "Not Found",
+        503 => "Service Unavailable",
+        _ => "Internal Server Error",
+    }
+}
+
+fn write_response(stream: &mut TcpStream, status: u16, content_type: &str, body: &[u8]) -> Result<(), String> {
+    let mut head = format!("HTTP/1.1 {} {}\r\nContent-Length: {}\r\nConnection: close\r\n", status, status_text(status), body.len());
+    if !body.is_empty() {
+        head.push_str(&format!("Content-Type: {}\r\n", content_type));
+    }
+    if status == 401 {
+        head.push_str("WWW-Authenticate: Bearer\r\n");
+    }
+    head.push_str("\r\n");
+    stream.write_all(head.as_bytes()).map_err(|e| e.to_string())?;
+    stream.write_all(body).map_err(|e| e.to_string())
+}
+
+fn write_json(stream: &mut TcpStream, value: &impl Serialize) -> Result<(), String> {
+    let body = serde_json::to_vec(value).map_err(|e| e.to_string())?;
+    write_response(stream, 200, "application/json", &body)
+}
+
+/// Local HTTP server, bound to 127.0.0.1 by the capturing process while the API is enabled.
+/// Disabling the API or changing its token applies to the next request and closes open event streams.
+pub struct HttpServer {
+    capture: Arc<Capture>,
+    search: Arc<SearchEngine>,
+    /// Port the server listens on, once it has started
+    port: Mutex<Option<u16>>,
+    /// Settings the open event streams were authorized with
+    streams_settings: Mutex<HttpApiSettings>,
+    subscribers: Mutex<Vec<Sender<String>>>,
+}
+
+impl HttpServer {
+    /// Serves the API whenever this process is capturing and it's enabled
+    pub fn attach(capture: Arc<Capture>, search: Arc<SearchEngine>) -> Arc<Self> {
+        let server = Arc::new(Self {
+            capture: Arc::clone(&capture),
+            search,
+            port: Mutex::new(None),
+            streams_settings: Mutex::new(capture.settings.read().unwrap().http_api.clone()),
+            subscribers: Mutex::new(Vec::new()),
+        });
+
+        // Lives as long as the capture it's subscribed to
+        let subscribed = Arc::clone(&server);
+        capture.subscribe(move |event| match event {
+            CaptureEvent::CaptureStarted => subscribed.listen_if_enabled(),
+            CaptureEvent::SettingsChanged(settings) => {
+                subscribed.close_streams_on_access_change(&settings.http_api);
+                if subscribed.capture.is_capturing() {
+                    subscribed.listen_if_enabled();
+                }
+            }
+            event => {
+                if let Some(message) = event_message(event) {
+                    // The event name and payload fields map onto an SSE event
+                    subscribed.broadcast(format!("event: {}\ndata: {}\n\n", message["event"].as_str().unwrap_or_default(), message["payload"]));
+                }
+            }
+        });
+
+        if capture.is_capturing() {
+            server.listen_if_enabled();
+        }
+        server
+    }
+
+    fn listen_if_enabled(self: &Arc<Self>) {
+        let settings = self.capture.settings.read().unwrap().http_api.clone();
+        if !settings.enabled {
+            return;
+        }
+        let mut port = self.port.lock().unwrap();
+        match *port {
+            Some(current) if current != settings.port => {
+                println!("[HTTP] Still listening on port {}, restart to use port {}", current, settings.port);
+                return;
+            }
+            Some(_) => return,
+            None => {}
+        }
+
+        let listener = match TcpListener::bind((Ipv4Addr::LOCALHOST, settings.port)) {
+            Ok(listener) => listener,
+            Err(e) => {
+                println!("[HTTP] Failed to listen on port {}: {}", settings.port, e);
+                return;
+            }
+        };
+        println!("[HTTP] Listening on 127.0.0.1:{}", settings.port);
+        *port = Some(listener.local_addr().map_or(settings.port, |addr| addr.port()));
+
+        let server = Arc::clone(self);
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                match stream {
+                    Ok(stream) => {
+                        let server = Arc::clone(&server);
+                        thread::spawn(move || {
+                            if let Err(e) = server.serve(stream) {
+                                println!("[HTTP] Connection closed with error: {}", e);
+                            }
+                        });
+                    }
+                    Err(e) => println!("[HTTP] Failed to accept connection: {}", e),
+                }
+            }
+        });
+    }
+
+    // Streams were authorized once when they opened, so a new token or disabling the API ends them
+    fn close_streams_on_access_change(&self, settings: &HttpApiSettings) {
+        let mut streams_settings = self.streams_settings.lock().unwrap();
+        if streams_settings.enabled != settings.enabled || streams_settings.token != settings.token {
+            // Each stream ends once its sender is gone
+            self.subscribers.lock().unwrap().clear();
+        }
+        *streams_settings = settings.clone();
+    }
+
+    fn broadcast(&self, message: String) {
+        // Senders of closed streams fail and are dropped
+        self.subscribers.lock().unwrap().retain(|subscriber| subscriber.send(message.clone()).is_ok());
+    }
+
+    // Answers one request per connection
+    fn serve(&self, mut stream: TcpStream) -> Result<(), String> {
+        let result = Request::read(&stream).and_then(|request| {
+            self.authorize(&request)?;
+            let route = route(&request.method, &request.path).ok_or_else(|| HttpError::not_found("No such endpoint"))?;
+            self.respond(route, &request, &mut stream)
+        });
+        match result {
+            Ok(()) => Ok(()),
+            Err(error) => {
+                let body = serde_json::to_vec(&json!({ "error": error.message })).map_err(|e| e.to_string())?;
+                write_response(&mut stream, error.status, "application/json", &body)
+            }
+        }
+    }
+
+    fn authorize(&self, request: &Request) -> Result<(), HttpError> {
+        let settings = self.capture.settings.read().unwrap().http_api.clone();
+        if !settings.enabled {
+            return Err(HttpError::new(503, "HTTP API is disabled"));
+        }
+        let expected = settings.token.unwrap_or_default();
+        // EventSource can't set headers, so the event stream also takes the token as a parameter
+        let token = match request.bearer_token() {
+            Some(token) => Some(token),
+            None if request.path == "/events" => request.query.get("token").map(String::as_str),
+            None => None,
+        };
+        match token {
+            Some(token) if !expected.is_empty() && constant_time_eq(token.as_bytes(), expected.as_bytes()) => Ok(()),
+            _ => Err(HttpError::new(401, "Missing or invalid bearer token")),
+        }
+    }
+
+    fn respond(&self, route: Route, request: &Request, stream: &mut TcpStream) -> Result<(), HttpError> {
+        let query = &request.query;
+        match route {
+            Route::ListEntries => {
+                let filter = EntryFilter {
+                    content_type: match query.get("type").map(String::as_str) {
+                        None => None,
+                        Some("text") => Some(ContentType::Text),
+                        Some("image") => Some(ContentType::Image),
+                        Some(other) => return Err(HttpError::bad_request(format!("Unknown type {:?}, expected text or image", other))),
+                    },
+                    source_app: query.get("app").cloned(),
+                };
+                let cursor = query.get("cursor").map(|cursor| parse_cursor(cursor)).transpose().map_err(HttpError::bad_request)?;
+                let limit = query_limit(query)?;
+                let page = self.capture.db.read(|db| db.list_entries(cursor.as_ref(), limit, &filter))?;
+                Ok(write_json(stream, &page)?)
+            }
+            Route::GetEntry(id) => Ok(write_json(stream, &self.get_entry(id)?)?),
+            Route::EntryImage(id) => {
+                let entry = self.get_entry(id)?;
+                if entry.content_type != ContentType::Image {
+                    return Err(HttpError::not_found(format!("Entry {} is not an image", id)));
+                }
+                Ok(write_response(stream, 200, "image/png", &entry.image_bytes()?)?)
+            }
+            Route::CopyEntry(id) => {
+                set_clipboard_entry(&self.get_entry(id)?)?;
+                Ok(write_response(stream, 204, "", &[])?)
+            }
+            Route::Search => {
+                let text = query.get("q").ok_or_else(|| HttpError::bad_request("Missing query parameter q"))?;
+                let entries = search_entries(&self.capture, &self.search, text, query_limit(query)?)?;
+                Ok(write_json(stream, &entries)?)
+            }
+            Route::Events => {
+                // Registered before the headers go out, so the client misses nothing after them
+                let (sender, receiver) = mpsc::channel();
+                self.subscribers.lock().unwrap().push(sender);
+                Ok(stream_events(stream, receiver)?)
+            }
+        }
+    }
+
+    fn get_entry(&self, id: i64) -> Result<ClipboardEntry, HttpError> {
+        self.capture.db.read(|db| db.get_entry(id))?.ok_or_else(|| HttpError::not_found(format!("Entry {} not found", id)))
+    }
+}
+
+// Writes events until the client disconnects
+fn stream_events(stream: &mut TcpStream, receiver: Receiver<String>) -> Result<(), String> {
+    let head = "HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nCache-Control: no-cache\r\nConnection: close\r\n\r\n";
+    stream.write_all(head.as_bytes()).map_err(|e| e.to_string())?;
+    loop {
+        let message = match receiver.recv_timeout(KEEPALIVE_INTERVAL) {
+            Ok(message) => message,
+            Err(RecvTimeoutError::Timeout) => ": keepalive\n\n".to_string(),
+            Err(RecvTimeoutError::Disconnected) => return Ok(()),
+        };
+        stream.write_all(message.as_bytes()).map_err(|e| e.to_string())?;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::capture::TestCapture;
+    use crate::settings::Settings;
+
+    const TOKEN: &str = "test-token";
+
+    // Listens on a free port, returned with the server
+    fn test_server(name: &str) -> (TestCapture, Arc<HttpServer>, u16) {
+        let capture = TestCapture::new(&format!("http_{}", name));
+        let http_api = HttpApiSettings { enabled: true, port: 0, token: Some(TOKEN.to_string()) };
+        *capture.settings.write().unwrap() = Settings { http_api, ..Settings::default() };
+        let server = HttpServer::attach(Arc::clone(&capture), Arc::new(SearchEngine::new()));
+        server.listen_if_enabled();
+        let port = server.port.lock().unwrap().unwrap();
+        (capture, server, port)
+    }
+
+    // Sends a request and returns the status and body
+    fn send(port: u16, request: &str) -> (u16, Vec<u8>) {
+        let mut stream = TcpStream::connect((Ipv4Addr::LOCALHOST, port)).unwrap();
+        stream.write_all(request.as_bytes()).unwrap();
+        let mut response = Vec::new();
+        stream.read_to_end(&mut response).unwrap();
+        let status = String::from_utf8_lossy(&response[9..12]).parse().unwrap();
+        let body_start = response.windows(4).position(|window| window == b"\r\n\r\n").unwrap() + 4;
+        (status, response[body_start..].to_vec())
+    }
+
+    fn get(port: u16, path: &str, token: &str) -> (u16, Vec<u8>) {
+        send(port, &format!("GET {} HTTP/1.1\r\nHost: localhost\r\nAuthorization: Bearer {}\r\n\r\n", path, token))
+    }
+
+    #[test]
+    fn test_routes() {
+        assert_eq!(route("GET", "/entries"), Some(Route::ListEntries));
+        assert_eq!(route("GET", "/entries/5/"), Some(Route::GetEntry(5)));
+        assert_eq!(route("GET", "/entries/5/image"), Some(Route::EntryImage(5)));
+        assert_eq!(route("POST", "/entries/5/copy"), Some(Route::CopyEntry(5)));
+        assert_eq!(route("GET", "/entries/5/copy"), None);
+        assert_eq!(route("GET", "/entries/abc"), None);
+        assert_eq!(route("GET", "/events"), Some(Route::Events));
+    }
+
+    #[test]
+    fn test_parse_query_and_cursor() {
+        let query = parse_query("q=hello+world&app=Visual%20Studio%20Code&flag");
+        assert_eq!(query["q"], "hello world");
+        assert_eq!(query["app"], "Visual Studio Code");
+        assert_eq!(query["flag"], "");
+        assert_eq!(parse_query("bad=%zz")["bad"], "%zz");
+
+        let cursor = parse_cursor("42:2024-01-01 10:00:00 UTC").unwrap();
+        assert_eq!(cursor, EntryCursor { created_at: "2024-01-01 10:00:00 UTC".to_string(), id: 42 });
+        assert!(parse_cursor("nope").is_err());
+    }
+
+    #[test]
+    fn test_settings_validation_and_redaction() {
+        assert!(HttpApiSettings::default().validate().is_ok());
+        assert!(HttpApiSettings { enabled: true, ..HttpApiSettings::default() }.validate().is_err());
+
+        let settings = HttpApiSettings { token: Some(TOKEN.to_string()), ..HttpApiSettings::default() };
+        assert!(!format!("{:?}", settings).contains(TOKEN));
+    }
+
+    #[test]
+    fn test_requests_need_the_token() {
+        let (capture, _server, port) = test_server("auth");
+        let id = capture.db.save_entry(ClipboardEntry::new_text_entry("hello".to_string())).unwrap();
+
+        assert_eq!(get(port, "/entries", "wrong").0, 401);
+        assert_eq!(send(port, "GET /entries HTTP/1.1\r\n\r\n").0, 401);
+
+        let (status, body) = get(port, "/entries?limit=5&type=text", TOKEN);
+        assert_eq!(status, 200);
+        let page: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(page["entries"][0]["id"], json!(id));
+
+        assert_eq!(get(port, &format!("/entries/{}", id), TOKEN).0, 200);
+        assert_eq!(get(port, &format!("/entries/{}/image", id), TOKEN).0, 404);
+        assert_eq!(get(port, "/entries/999", TOKEN).0, 404);
+        assert_eq!(get(port, "/nowhere", TOKEN).0, 404);
+
+        // Disabling applies without a restart
+        capture.settings.write().unwrap().http_api.enabled = false;
+        assert_eq!(get(port, "/entries", TOKEN).0, 503);
+    }
+
+    #[test]
+    fn test_image_bytes() {
+        let (capture, _server, port) = test_server("image");
+        let image = ClipboardEntry::new_image_entry("data:image/png;base64,iVBORw0KGgo=".to_string());
+        let id = capture.db.save_entry(image).unwrap();
+
+        let (status, body) = get(port, &format!("/entries/{}/image", id), TOKEN);
+        assert_eq!(status, 200);
+        assert!(body.starts_with(b"\x89PNG"));
+    }
+
+    #[test]
+    fn test_event_stream() {
+        let (capture, _server, port) = test_server("events");
+
+        let mut stream = TcpStream::connect((Ipv4Addr::LOCALHOST, port)).unwrap();
+        write!(stream, "GET /events?token={} HTTP/1.1\r\n\r\n", TOKEN).unwrap();
+        let mut lines = BufReader::new(stream).lines();
+        assert_eq!(lines.next().unwrap().unwrap(), "HTTP/1.1 200 OK");
+        // Skip the rest of the headers
+        while !lines.next().unwrap().unwrap().is_empty() {}
+
+        capture.emit(CaptureEvent::EntryDeleted(7));
+        assert_eq!(lines.next().unwrap().unwrap(), "event: clipboard-deleted");
+        assert_eq!(lines.next().unwrap().unwrap(), "data: 7");
+        assert_eq!(lines.next().unwrap().unwrap(), "");
+
+        // Other settings leave the stream open, a new token closes it
+        let mut settings = capture.settings.read().unwrap().clone();
+        settings.max_entries = Some(10);
+        capture.emit(CaptureEvent::SettingsChanged(settings.clone()));
+        capture.emit(CaptureEvent::EntryDeleted(8));
+        assert_eq!(lines.next().unwrap().unwrap(), "event: clipboard-deleted");
+        assert_eq!(lines.next().unwrap().unwrap(), "data: 8");
+        assert_eq!(lines.next().unwrap().unwrap(), "");
+        settings.http_api.token = Some("new-token".to_string());
+        capture.emit(CaptureEvent::SettingsChanged(settings));
+        assert!(lines.next().is_none());
+    }
+}
@@ -1,108 +1,470 @@
 // Learn more about Tauri commands at https://tauri.app/develop/calling-rust/
 use tauri::image::Image;
+pub mod app_filter;
+#[cfg(target_os = "macos")]
 pub mod base;
+#[cfg(target_os = "linux")]
+#[path = "base_linux.rs"]
+pub mod base;
+pub mod capture;
+pub mod cli;
+pub mod crypto;
 pub mod db;
+pub mod db_actor;
+#[cfg(target_os = "linux")]
+pub mod dbus;
+pub mod hooks;
+pub mod http_api;
+pub mod ipc;
+pub mod markers;
+pub mod secrets;
 mod model;
 mod fzf;
+mod normalize;
+pub mod patterns;
+pub mod pause;
+pub mod pipeline;
+pub mod rewrite;
+mod position;
+mod search;
+mod tray;
+pub mod settings;
+pub mod webhook;
 
-use db::{ClipboardDatabase, ClipboardEntry};
-use std::sync::Mutex;
-use std::thread;
+use crypto::KeySource;
+use db::{ClipboardEntry, EncryptionStatus, EntryCounts, EntryCursor, EntryFilter, EntryPage, EntrySummary, DB_FILE_NAME};
+use capture::{Capture, CaptureEvent};
+use http_api::HttpServer;
+use ipc::IpcServer;
+use pause::PauseStatus;
+use position::{Bounds, WindowPosition};
+use search::{SearchEngine, SearchHit};
+use settings::{Settings, SETTINGS_FILE_NAME};
+use std::sync::{Arc, Mutex};
 use std::time::Duration;
-use tauri::{AppHandle, Emitter, Manager, State};
-use tauri::menu::{Menu, MenuItem};
+use tauri::{AppHandle, Emitter, Listener, Manager, State, WebviewWindow};
+use tauri::menu::{Menu, MenuItem, PredefinedMenuItem};
 use tauri::tray::{MouseButton, MouseButtonState, TrayIconBuilder, TrayIconEvent};
-use base::{get_current_clipboard_count, get_clipboard_text, get_clipboard_image};
+use tauri_plugin_global_shortcut::{GlobalShortcutExt, Shortcut, ShortcutState};
+use app_filter::AppFilter;
+use base::set_clipboard_entry;
+
+const TRAY_ID: &str = "main";
 
-// Application state to hold the database connection
+// Application state to hold the database handle
 struct AppState {
-    db: Mutex<ClipboardDatabase>,
+    capture: Arc<Capture>,
+    search: Arc<SearchEngine>,
     last_tray_rect: Mutex<Option<tauri::Rect>>,
+    /// Why the configured hotkey couldn't be registered at startup, if it couldn't
+    hotkey_error: Mutex<Option<String>>,
 }
 
-fn save_clipboard_event(
-    state: State<AppState>,
-    clipboard_entry: ClipboardEntry,
-) -> Result<i64, String> {
-    let db = state.db.lock().map_err(|e| e.to_string())?;
-    let id = db.save_entry(clipboard_entry.clone()).map_err(|e| e.to_string())?;
-    println!("Clipboard entry saved with id: {:?}", id);
-    Ok(id)
+#[derive(Debug, Clone, serde::Serialize)]
+struct HotkeyStatus {
+    hotkey: Option<String>,
+    registered: bool,
+    error: Option<String>,
+}
+
+// Tray menu items whose text changes at runtime
+struct TrayMenu {
+    // Replaced each time the menu is rebuilt
+    pause_item: Mutex<MenuItem<tauri::Wry>>,
+}
+
+// Updates the tray menu and the frontend after the pause state changes
+fn notify_pause_changed(app_handle: &AppHandle) {
+    let state = app_handle.state::<AppState>();
+    let now = chrono::Utc::now();
+    if let Some(tray_menu) = app_handle.try_state::<TrayMenu>() {
+        let _ = tray_menu.pause_item.lock().unwrap().set_text(state.capture.pause.menu_label(now));
+    }
+    let _ = app_handle.emit("capture-pause-changed", state.capture.pause.status(now));
+}
+
+// Applies a change to the current settings, persists it and tells every window about it.
+// Updates run one at a time, so a change always starts from the result of the previous one.
+fn update_settings_with(app_handle: &AppHandle, change: impl FnOnce(&mut Settings)) -> Result<Settings, String> {
+    let state = app_handle.state::<AppState>();
+    let update = state.capture.begin_settings_update()?;
+    let mut settings = state.capture.settings.read().map_err(|e| e.to_string())?.clone();
+    let previous_hotkey = settings.hotkey.clone();
+    change(&mut settings);
+    settings.validate()?;
+
+    // Registered again on every save, so a shortcut that was unavailable gets another try
+    let hotkey_changed = settings.hotkey != previous_hotkey;
+    match register_hotkey(app_handle, previous_hotkey.as_deref(), settings.hotkey.as_deref()) {
+        Ok(()) => *state.hotkey_error.lock().map_err(|e| e.to_string())? = None,
+        // A conflicting new shortcut rejects the whole update and keeps the old one active
+        Err(e) if hotkey_changed => return Err(e),
+        // The same shortcut is still unavailable, which shouldn't hold up the other settings
+        Err(e) => *state.hotkey_error.lock().map_err(|e| e.to_string())? = Some(e),
+    }
+
+    if let Err(e) = state.capture.apply_settings(&update, settings.clone()) {
+        // The previous settings stay in effect, and so must their shortcut
+        if hotkey_changed {
+            if let Err(hotkey_error) = register_hotkey(app_handle, settings.hotkey.as_deref(), previous_hotkey.as_deref()) {
+                *state.hotkey_error.lock().map_err(|e| e.to_string())? = Some(hotkey_error);
+            }
+        }
+        return Err(e);
+    }
+    Ok(settings)
+}
+
+// Swaps the global shortcut. The previous one is released first, so registering the
+// same shortcut again (or with different casing) works, and is put back if the next one fails.
+fn register_hotkey(app_handle: &AppHandle, previous: Option<&str>, next: Option<&str>) -> Result<(), String> {
+    let shortcuts = app_handle.global_shortcut();
+    let previous = previous
+        .and_then(|previous| previous.parse::<Shortcut>().ok())
+        .filter(|&shortcut| shortcuts.is_registered(shortcut));
+    if let Some(previous) = previous {
+        shortcuts.unregister(previous).map_err(|e| e.to_string())?;
+    }
+
+    let Some(next) = next else {
+        return Ok(());
+    };
+    let registered = next
+        .parse::<Shortcut>()
+        .map_err(|e| format!("Invalid hotkey {:?}: {}", next, e))
+        .and_then(|shortcut| {
+            shortcuts
+                .register(shortcut)
+                .map_err(|e| format!("Hotkey {} is unavailable, it may be used by another application: {}", next, e))
+        });
+    if let Err(e) = registered {
+        if let Some(previous) = previous {
+            let _ = shortcuts.register(previous);
+        }
+        return Err(e);
+    }
+    println!("Registered global hotkey: {}", next);
+    Ok(())
+}
+
+// Shows the history window, or hides it if it's already showing
+fn toggle_main_window(app_handle: &AppHandle) {
+    if let Some(window) = app_handle.get_webview_window("main") {
+        if window.is_visible().unwrap_or(false) {
+            let _ = window.hide();
+        } else {
+            show_main_window(app_handle);
+        }
+    }
+}
+
+fn show_main_window(app_handle: &AppHandle) {
+    if let Some(window) = app_handle.get_webview_window("main") {
+        if let Err(e) = position_main_window(app_handle, &window) {
+            println!("Failed to position window, centering instead: {}", e);
+            let _ = window.center();
+        }
+        let _ = window.show();
+        let _ = window.set_focus();
+    }
+}
+
+// Places the window as the window_position setting asks, centering it when there's nothing to anchor to
+fn position_main_window(app_handle: &AppHandle, window: &WebviewWindow) -> Result<(), String> {
+    let state = app_handle.state::<AppState>();
+    let mode = state.capture.settings.read().map_err(|e| e.to_string())?.window_position;
+    let size = window.outer_size().map_err(|e| e.to_string())?;
+    let (width, height) = (size.width as f64, size.height as f64);
+
+    let origin = match mode {
+        WindowPosition::Center => None,
+        WindowPosition::TrayIcon => {
+            // Nothing is stored until the tray icon has been clicked once
+            let tray_rect = *state.last_tray_rect.lock().map_err(|e| e.to_string())?;
+            tray_rect.and_then(|rect| {
+                let scale = window.scale_factor().unwrap_or(1.0);
+                let position = rect.position.to_physical::<f64>(scale);
+                let size = rect.size.to_physical::<f64>(scale);
+                let tray = Bounds { x: position.x, y: position.y, width: size.width, height: size.height };
+                let monitor = monitor_bounds(app_handle, tray.x + tray.width / 2.0, tray.y + tray.height / 2.0)?;
+                Some(position::below_tray_icon(tray, width, height, monitor))
+            })
+        }
+        WindowPosition::Cursor => {
+            let cursor = app_handle.cursor_position().map_err(|e| e.to_string())?;
+            monitor_bounds(app_handle, cursor.x, cursor.y)
+                .map(|monitor| position::at_cursor(cursor.x, cursor.y, width, height, monitor))
+        }
+    };
+
+    match origin {
+        Some((x, y)) => window.set_position(tauri::PhysicalPosition::new(x, y)),
+        None => window.center(),
+    }
+    .map_err(|e| e.to_string())
+}
+
+// Usable area (excluding menu bar and dock) of the monitor containing the point, in physical pixels
+fn monitor_bounds(app_handle: &AppHandle, x: f64, y: f64) -> Option<Bounds> {
+    let monitors = app_handle.available_monitors().ok()?;
+    let monitor = monitors.iter().find(|monitor| {
+        let position = monitor.position();
+        let size = monitor.size();
+        Bounds { x: position.x as f64, y: position.y as f64, width: size.width as f64, height: size.height as f64 }
+            .contains(x, y)
+    })?;
+    let area = monitor.work_area();
+    Some(Bounds {
+        x: area.position.x as f64,
+        y: area.position.y as f64,
+        width: area.size.width as f64,
+        height: area.size.height as f64,
+    })
+}
+
+// Builds the tray menu: pinned and recent entries, then the fixed items
+fn build_tray_menu(app_handle: &AppHandle) -> tauri::Result<Menu<tauri::Wry>> {
+    let menu = Menu::new(app_handle)?;
+
+    let entries = tray_entries(app_handle).unwrap_or_else(|e| {
+        // The database may still be locked
+        println!("Failed to load entries for tray menu: {}", e);
+        Vec::new()
+    });
+    for entry in &entries {
+        let item = MenuItem::with_id(app_handle, tray::entry_item_id(entry.id), tray::entry_label(entry), true, None::<&str>)?;
+        menu.append(&item)?;
+    }
+    if !entries.is_empty() {
+        menu.append(&PredefinedMenuItem::separator(app_handle)?)?;
+    }
+
+    let pause_label = app_handle.state::<AppState>().capture.pause.menu_label(chrono::Utc::now());
+    let open_item = MenuItem::with_id(app_handle, "open", "Open", true, None::<&str>)?;
+    let settings_item = MenuItem::with_id(app_handle, "settings", "Settings", true, None::<&str>)?;
+    let pause_item = MenuItem::with_id(app_handle, "pause", pause_label, true, None::<&str>)?;
+    let quit_item = MenuItem::with_id(app_handle, "quit", "Quit", true, None::<&str>)?;
+    menu.append_items(&[&open_item, &settings_item, &pause_item, &quit_item])?;
+
+    match app_handle.try_state::<TrayMenu>() {
+        Some(tray_menu) => *tray_menu.pause_item.lock().unwrap() = pause_item,
+        None => {
+            app_handle.manage(TrayMenu { pause_item: Mutex::new(pause_item) });
+        }
+    }
+    Ok(menu)
+}
+
+fn tray_entries(app_handle: &AppHandle) -> Result<Vec<EntrySummary>, String> {
+    let state = app_handle.state::<AppState>();
+    let recent_count = state.capture.settings.read().map_err(|e| e.to_string())?.tray_recent_count;
+    let pinned = state.capture.db.read(|db| db.get_pinned_summaries())?;
+    // Pinned entries may be among the most recent, so fetch enough to fill the rest
+    let limit = recent_count + pinned.len();
+    let recent = state.capture.db.read(|db| db.list_entries(None, limit, &EntryFilter::default()))?.entries;
+    Ok(tray::menu_entries(pinned, recent, recent_count))
+}
+
+fn refresh_tray_menu(app_handle: &AppHandle) {
+    let Some(tray_icon) = app_handle.tray_by_id(TRAY_ID) else {
+        return;
+    };
+    match build_tray_menu(app_handle) {
+        Ok(menu) => {
+            let _ = tray_icon.set_menu(Some(menu));
+        }
+        Err(e) => println!("Failed to rebuild tray menu: {}", e),
+    }
+}
+
+// Puts a stored entry back on the clipboard
+fn copy_entry_to_clipboard(app_handle: &AppHandle, id: i64) -> Result<(), String> {
+    let entry = app_handle
+        .state::<AppState>()
+        .capture
+        .db
+        .read(|db| db.get_entry(id))?
+        .ok_or_else(|| format!("Entry {} not found", id))?;
+    set_clipboard_entry(&entry)
 }
 
 #[tauri::command]
-fn delete_clipboard_entry(id: i64, state: State<AppState>, app_handle: AppHandle) -> Result<i64, String> {
+fn delete_clipboard_entry(id: i64, state: State<AppState>) -> Result<i64, String> {
     println!("Deleting clipboard entry with id: {:?}", id);
-    let db = state.db.lock().map_err(|e| e.to_string())?;
-    let deleted_id = db.delete_entry(id).map_err(|e| e.to_string())?;
+    let deleted_id = state.capture.db.delete_entry(id)?;
     println!("Clipboard entry deleted with id: {:?}", id);
-    app_handle.emit("clipboard-deleted", deleted_id).map_err(|e| e.to_string())?;
+    state.capture.emit(CaptureEvent::EntryDeleted(deleted_id));
     Ok(deleted_id)
 }
 
 #[tauri::command]
-fn load_clipboard_events_at_startup(state: State<AppState>) -> Result<Vec<ClipboardEntry>, String> {
-    println!("Loading clipboard events at startup");
-    let db = state.db.lock().map_err(|e| e.to_string())?;
-    let entries = db.get_all_entries().map_err(|e| e.to_string())?;
-    println!("Loaded {} entries from database", entries.len());
-    for entry in &entries {
-        println!("  Entry: {:?}", entry);
+fn list_entries(
+    cursor: Option<EntryCursor>,
+    limit: usize,
+    filters: Option<EntryFilter>,
+    state: State<AppState>,
+) -> Result<EntryPage, String> {
+    let filters = filters.unwrap_or_default();
+    state.capture.db.read(|db| db.list_entries(cursor.as_ref(), limit, &filters))
+}
+
+#[tauri::command]
+fn get_entry_summaries(ids: Vec<i64>, state: State<AppState>) -> Result<Vec<EntrySummary>, String> {
+    state.capture.db.read(|db| db.get_summaries(&ids))
+}
+
+#[tauri::command]
+fn get_entry_counts(state: State<AppState>) -> Result<EntryCounts, String> {
+    state.capture.db.read(|db| db.count_entries_by_type())
+}
+
+// Serves `entry://localhost/entries/<id>/image`, the thumbnail of an EntrySummary
+fn serve_entry_image(app_handle: &AppHandle, path: &str) -> tauri::http::Response<Vec<u8>> {
+    let response = |status: u16, content_type: &str, body: Vec<u8>| {
+        tauri::http::Response::builder()
+            .status(status)
+            .header("Content-Type", content_type)
+            .body(body)
+            .unwrap()
+    };
+    let Some(http_api::Route::EntryImage(id)) = http_api::route("GET", path) else {
+        return response(404, "text/plain", b"Not found".to_vec());
+    };
+    let state = app_handle.state::<AppState>();
+    match state.capture.db.read(|db| db.get_entry(id)).and_then(|entry| {
+        entry.ok_or(format!("Entry {} not found", id))?.image_bytes()
+    }) {
+        Ok(bytes) => response(200, "image/png", bytes),
+        Err(e) => response(404, "text/plain", e.into_bytes()),
     }
-    Ok(entries)
 }
 
 #[tauri::command]
-fn hide_window(app_handle: AppHandle) -> Result<(), String> {
-    let window = app_handle.get_webview_window("main").ok_or("Main window not found".to_string())?;
-    window.hide().map_err(|e| e.to_string())?;
-    println!("Window hidden successfully");
+fn get_clipboard_entry(id: i64, state: State<AppState>) -> Result<Option<ClipboardEntry>, String> {
+    state.capture.db.read(|db| db.get_entry(id))
+}
+
+#[tauri::command]
+fn copy_clipboard_entry(id: i64, app_handle: AppHandle) -> Result<(), String> {
+    println!("Copying clipboard entry with id: {:?}", id);
+    copy_entry_to_clipboard(&app_handle, id)
+}
+
+#[tauri::command]
+fn set_clipboard_entry_pinned(id: i64, pinned: bool, state: State<AppState>) -> Result<(), String> {
+    println!("Setting pinned = {} for clipboard entry with id: {:?}", pinned, id);
+    if !state.capture.db.write(move |db| db.set_pinned(id, pinned))? {
+        return Err(format!("Entry {} not found", id));
+    }
+    state.capture.emit(CaptureEvent::EntryPinned { id, pinned });
     Ok(())
 }
 
-fn spawn_clipboard_polling_thread(app_handle: AppHandle) -> Result<(), String> {
-    let mut current_count = 0;
-    println!("[POLLING] Spawning clipboard polling thread");
-    thread::spawn(move || loop {
-        let new_count = get_current_clipboard_count();
+#[tauri::command]
+async fn search_clipboard_entries(query: String, state: State<'_, AppState>) -> Result<Option<Vec<SearchHit>>, String> {
+    // Scoring a large history takes a while, so keep it off the async runtime threads
+    let search = Arc::clone(&state.search);
+    // None means a newer query superseded this one
+    tauri::async_runtime::spawn_blocking(move || search.search(&query))
+        .await
+        .map_err(|e| e.to_string())
+}
 
-        if new_count == current_count {
-            thread::sleep(Duration::from_secs(1));
-            continue;
-        }
+#[tauri::command]
+fn get_encryption_status(state: State<AppState>) -> Result<EncryptionStatus, String> {
+    state.capture.db.read(|db| db.encryption_status())
+}
 
-        println!("[POLLING] Clipboard count changed: {} -> {}", current_count, new_count);
-        current_count = new_count.clone();
+#[tauri::command]
+fn enable_encryption(source: KeySource, state: State<AppState>) -> Result<EncryptionStatus, String> {
+    state.capture.db.write(move |db| db.enable_encryption(&source))?;
+    state.capture.db.read(|db| db.encryption_status())
+}
 
-        let mut entry = if let Some(text) = get_clipboard_text() {
-            println!("[POLLING] Detected text entry");
-            ClipboardEntry::new_text_entry(text)
-        } else if let Some(image_path) = get_clipboard_image(new_count) {
-            println!("[POLLING] Detected image entry");
-            ClipboardEntry::new_image_entry(image_path)
-        } else {
-            println!("[POLLING] No text or image detected, skipping");
-            thread::sleep(Duration::from_secs(1));
-            continue;
-        };
+#[tauri::command]
+fn disable_encryption(state: State<AppState>) -> Result<EncryptionStatus, String> {
+    state.capture.db.write(|db| db.disable_encryption())?;
+    state.capture.db.read(|db| db.encryption_status())
+}
 
+#[tauri::command]
+fn unlock_database(source: KeySource, state: State<AppState>, app_handle: AppHandle) -> Result<EncryptionStatus, String> {
+    state.capture.db.read(|db| db.unlock(&source))?;
+    // Entries could not be indexed or listed in the tray while the database was locked
+    let texts = state.capture.db.read(|db| db.get_text_contents())?;
+    state.search.reset(&texts);
+    refresh_tray_menu(&app_handle);
+    state.capture.db.read(|db| db.encryption_status())
+}
 
-        match save_clipboard_event(app_handle.state::<AppState>(), entry.clone()) {
-            Ok(id) => {
-                println!("[POLLING] Entry saved with id: {}", id);
-                entry.id = Some(id);
-            }
-            Err(e) => {
-                println!("[POLLING] Error saving clipboard event: {:?}", e);
-            }
-        }
+#[tauri::command]
+fn get_pause_status(state: State<AppState>) -> PauseStatus {
+    state.capture.pause.status(chrono::Utc::now())
+}
+
+#[tauri::command]
+fn pause_capture(state: State<AppState>) -> Result<PauseStatus, String> {
+    println!("Pausing clipboard capture");
+    state.capture.pause()?;
+    Ok(state.capture.pause.status(chrono::Utc::now()))
+}
 
-        println!("[POLLING] Emitting clipboard-changed event with id: {:?}", entry.id);
-        // 프론트엔드로 이벤트 emit
-        app_handle.emit("clipboard-changed", entry).unwrap();
+#[tauri::command]
+fn pause_capture_for(minutes: u64, state: State<AppState>) -> Result<PauseStatus, String> {
+    println!("Pausing clipboard capture for {} minutes", minutes);
+    state.capture.pause_for(Duration::from_secs(minutes.saturating_mul(60)))?;
+    Ok(state.capture.pause.status(chrono::Utc::now()))
+}
 
-        thread::sleep(Duration::from_secs(1));
-    });
+#[tauri::command]
+fn resume_capture(state: State<AppState>) -> Result<PauseStatus, String> {
+    println!("Resuming clipboard capture");
+    state.capture.resume()?;
+    Ok(state.capture.pause.status(chrono::Utc::now()))
+}
+
+#[tauri::command]
+fn get_app_filter(state: State<AppState>) -> Result<AppFilter, String> {
+    let settings = state.capture.settings.read().map_err(|e| e.to_string())?;
+    Ok(settings.app_filter.clone())
+}
+
+#[tauri::command]
+fn set_app_filter(filter: AppFilter, app_handle: AppHandle) -> Result<(), String> {
+    println!("Updating application filter: {:?}", filter);
+    update_settings_with(&app_handle, |settings| settings.app_filter = filter)?;
+    Ok(())
+}
+
+#[tauri::command]
+fn get_settings(state: State<AppState>) -> Result<Settings, String> {
+    let settings = state.capture.settings.read().map_err(|e| e.to_string())?;
+    Ok(settings.clone())
+}
+
+#[tauri::command]
+fn update_settings(settings: Settings, app_handle: AppHandle) -> Result<Settings, String> {
+    update_settings_with(&app_handle, |current| *current = settings)
+}
+
+/// Replaces the HTTP API token, so clients holding the old one lose access
+#[tauri::command]
+fn generate_http_api_token(app_handle: AppHandle) -> Result<Settings, String> {
+    update_settings_with(&app_handle, |settings| settings.http_api.token = Some(crypto::generate_token()))
+}
+
+#[tauri::command]
+fn get_hotkey_status(state: State<AppState>) -> Result<HotkeyStatus, String> {
+    let hotkey = state.capture.settings.read().map_err(|e| e.to_string())?.hotkey.clone();
+    let error = state.hotkey_error.lock().map_err(|e| e.to_string())?.clone();
+    Ok(HotkeyStatus { registered: hotkey.is_some() && error.is_none(), hotkey, error })
+}
+
+#[tauri::command]
+fn hide_window(app_handle: AppHandle) -> Result<(), String> {
+    let window = app_handle.get_webview_window("main").ok_or("Main window not found".to_string())?;
+    window.hide().map_err(|e| e.to_string())?;
+    println!("Window hidden successfully");
     Ok(())
 }
 
@@ -117,42 +479,92 @@ pub fn run() {
             std::fs::create_dir_all(&app_data_dir)
                 .expect("Failed to create app data directory");
 
-            let db_path = app_data_dir.join("clipboard_history.db");
-            println!("Database path: {:?}", db_path);
+            let settings_path = app.path().app_config_dir()
+                .expect("Failed to get app config directory")
+                .join(SETTINGS_FILE_NAME);
 
-            let db = ClipboardDatabase::new(db_path)
+            // Attaches to the same database as a running `cw daemon`
+            let capture = Capture::open(app_data_dir.join(DB_FILE_NAME), settings_path)
                 .expect("Failed to initialize database");
+            let capture = Arc::new(capture);
+
+            // A passphrase-protected database stays locked until unlock_database is called
+            let search = Arc::new(SearchEngine::new());
+            match capture.db.read(|db| db.get_text_contents()) {
+                Ok(texts) => search.reset(&texts),
+                Err(e) => println!("Failed to load entries for search index: {}", e),
+            }
 
             // Create and register AppState
             app.manage(AppState {
-                db: Mutex::new(db),
+                capture: Arc::clone(&capture),
+                search: Arc::clone(&search),
                 last_tray_rect: Mutex::new(None),
+                hotkey_error: Mutex::new(None),
+            });
+
+            let hotkey = capture.settings.read().unwrap().hotkey.clone();
+            if let Err(e) = register_hotkey(app.handle(), None, hotkey.as_deref()) {
+                // Reported to the settings window through get_hotkey_status
+                println!("Failed to register global hotkey: {}", e);
+                *app.state::<AppState>().hotkey_error.lock().unwrap() = Some(e);
+            }
+
+            // Keep the search index in sync with the history
+            let index_handle = app.handle().clone();
+            app.listen("clipboard-changed", move |event| {
+                if let Ok(entry) = serde_json::from_str::<ClipboardEntry>(event.payload()) {
+                    index_handle.state::<AppState>().search.insert_entry(&entry);
+                }
+            });
+            let index_handle = app.handle().clone();
+            app.listen("clipboard-deleted", move |event| {
+                if let Ok(id) = serde_json::from_str::<i64>(event.payload()) {
+                    index_handle.state::<AppState>().search.remove_entry(id);
+                }
             });
             let icon_bytes = include_bytes!("../icons/icon32_32.png");
             let icon = Image::from_bytes(icon_bytes)?;
 
-            let app_handle = app.handle().clone();
-            spawn_clipboard_polling_thread(app_handle.clone())?;
+            // Forward capture events to the windows
+            let event_handle = app.handle().clone();
+            capture.subscribe(move |event| match event {
+                CaptureEvent::EntryAdded(entry) => {
+                    let _ = event_handle.emit("clipboard-changed", entry);
+                }
+                CaptureEvent::EntryDeleted(id) => {
+                    let _ = event_handle.emit("clipboard-deleted", id);
+                }
+                CaptureEvent::EntryPinned { id, pinned } => {
+                    let _ = event_handle.emit("clipboard-pinned", serde_json::json!({ "id": id, "pinned": pinned }));
+                }
+                CaptureEvent::PauseChanged(_) => notify_pause_changed(&event_handle),
+                CaptureEvent::SettingsChanged(settings) => {
+                    let _ = event_handle.emit("settings-changed", settings);
+                }
+                CaptureEvent::CaptureStarted => {}
+            });
+            // Serves scripts whenever this process is the one capturing
+            IpcServer::attach(Arc::clone(&capture), Arc::clone(&search));
+            #[cfg(target_os = "linux")]
+            dbus::attach(Arc::clone(&capture), Arc::clone(&search));
+            HttpServer::attach(Arc::clone(&capture), search);
+            hooks::attach(Arc::clone(&capture));
+            webhook::attach(Arc::clone(&capture));
+            // Only follows the history while a daemon is capturing
+            capture.start()?;
 
             // Create tray icon with menu
-            let open_item = MenuItem::with_id(app, "open", "Open", true, None::<&str>)?;
-            let settings_item = MenuItem::with_id(app, "settings", "Settings", true, None::<&str>)?;
-            let quit_item = MenuItem::with_id(app, "quit", "Quit", true, None::<&str>)?;
-            let menu = Menu::with_items(app, &[&open_item, &settings_item, &quit_item])?;
+            let menu = build_tray_menu(app.handle())?;
 
-            let _tray = TrayIconBuilder::new()
+            let _tray = TrayIconBuilder::with_id(TRAY_ID)
                 .icon(icon)
                 .icon_as_template(true)
                 .menu(&menu)
                 .on_menu_event(|app, event| {
                     match event.id().as_ref() {
                         "open" => {
-                            if let Some(window) = app.get_webview_window("main") {
-                                // Center window on screen
-                                let _ = window.center();
-                                let _ = window.show();
-                                let _ = window.set_focus();
-                            }
+                            show_main_window(app);
                         }
                         "settings" => {
                             // Check if settings window already exists
@@ -173,10 +585,27 @@ pub fn run() {
                                 .build();
                             }
                         }
+                        "pause" => {
+                            let state = app.state::<AppState>();
+                            let result = if state.capture.pause.is_paused(chrono::Utc::now()) {
+                                state.capture.resume()
+                            } else {
+                                state.capture.pause()
+                            };
+                            if let Err(e) = result {
+                                println!("Failed to toggle pause from tray: {}", e);
+                            }
+                        }
                         "quit" => {
                             app.exit(0);
                         }
-                        _ => {}
+                        item_id => {
+                            if let Some(id) = tray::entry_id_from_item(item_id) {
+                                if let Err(e) = copy_entry_to_clipboard(app, id) {
+                                    println!("Failed to copy entry {} from tray: {}", id, e);
+                                }
+                            }
+                        }
                     }
                 })
                 .on_tray_icon_event(|tray, event| {
@@ -197,20 +626,17 @@ pub fn run() {
                             }
                         }
 
-                        if let Some(window) = app.get_webview_window("main") {
-                            if window.is_visible().unwrap_or(false) {
-                                let _ = window.hide();
-                            } else {
-                                // Center window on screen
-                                let _ = window.center();
-                                let _ = window.show();
-                                let _ = window.set_focus();
-                            }
-                        }
+                        toggle_main_window(app);
                     }
                 })
                 .build(app)?;
 
+            // Keep the entries listed in the tray menu current
+            for event in ["clipboard-changed", "clipboard-deleted", "clipboard-pinned", "settings-changed"] {
+                let tray_handle = app.handle().clone();
+                app.listen(event, move |_| refresh_tray_menu(&tray_handle));
+            }
+
             Ok(())
         })
         .on_window_event(|window, event| {
@@ -221,10 +647,41 @@ pub fn run() {
                 }
             }
         })
+        .register_uri_scheme_protocol("entry", |ctx, request| serve_entry_image(ctx.app_handle(), request.uri().path()))
         .plugin(tauri_plugin_opener::init())
+        .plugin(
+            tauri_plugin_global_shortcut::Builder::new()
+                .with_handler(|app, _shortcut, event| {
+                    // Only the history hotkey is registered
+                    if event.state() == ShortcutState::Pressed {
+                        toggle_main_window(app);
+                    }
+                })
+                .build(),
+        )
         .invoke_handler(tauri::generate_handler![
-            load_clipboard_events_at_startup,
+            list_entries,
+            get_entry_summaries,
+            get_entry_counts,
+            get_clipboard_entry,
+            get_encryption_status,
+            enable_encryption,
+            disable_encryption,
+            unlock_database,
+            get_pause_status,
+            pause_capture,
+            pause_capture_for,
+            resume_capture,
+            get_app_filter,
+            set_app_filter,
+            get_settings,
+            update_settings,
+            get_hotkey_status,
+            generate_http_api_token,
             delete_clipboard_entry,
+            search_clipboard_entries,
+            copy_clipboard_entry,
+            set_clipboard_entry_pinned,
             hide_window
         ])
         .run(tauri::generate_context!())
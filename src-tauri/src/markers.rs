@@ -0,0 +1,109 @@
+use serde::{Deserialize, Serialize};
+
+/// Set by password managers on macOS for copies that must not be shown or stored
+pub const CONCEALED_TYPE: &str = "org.nspasteboard.ConcealedType";
+/// Set on macOS for copies that only live on the clipboard briefly
+pub const TRANSIENT_TYPE: &str = "org.nspasteboard.TransientType";
+/// Set by KeePassXC and other password managers on Linux (KDE convention)
+pub const KDE_PASSWORD_MANAGER_HINT: &str = "x-kde-passwordManagerHint";
+/// Set by this app on entries it puts back on the clipboard, so they aren't recorded again
+pub const RESTORED_TYPE: &str = "com.hyukmac.clipboardwatcher.restored";
+
+// Plain text as offered by macOS and older apps, and on Linux, along with the targets
+// every X11 selection owner lists
+const PLAIN_TEXT_TYPES: &[&str] = &[
+    "public.utf8-plain-text",
+    "public.utf16-plain-text",
+    "public.plain-text",
+    "NSStringPboardType",
+    "text/plain",
+    "text/plain;charset=utf-8",
+    "UTF8_STRING",
+    "STRING",
+    "TEXT",
+    "TARGETS",
+    "TIMESTAMP",
+    "MULTIPLE",
+    "SAVE_TARGETS",
+];
+
+/// Text stored instead of a concealed copy when the policy asks for a placeholder
+pub const CONCEALED_PLACEHOLDER: &str = "••••••••";
+
+/// Clipboard markers that tell a clipboard manager to stay away from a copy
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ClipboardMarkers {
+    pub concealed: bool,
+    pub transient: bool,
+    pub restored: bool,
+    /// The copy offers more than plain text, e.g. HTML, RTF or files, which writing
+    /// plain text back to the clipboard would drop
+    pub other_formats: bool,
+}
+
+impl ClipboardMarkers {
+    /// Reads the markers from the type names (UTIs or MIME types) offered by the clipboard
+    pub fn from_types<'a>(types: impl IntoIterator<Item = &'a str>) -> Self {
+        let mut markers = Self::default();
+        for type_name in types {
+            match type_name {
+                CONCEALED_TYPE | KDE_PASSWORD_MANAGER_HINT => markers.concealed = true,
+                TRANSIENT_TYPE => markers.transient = true,
+                RESTORED_TYPE => markers.restored = true,
+                _ if PLAIN_TEXT_TYPES.contains(&type_name) => {}
+                _ => markers.other_formats = true,
+            }
+        }
+        markers
+    }
+}
+
+/// How to record a copy marked as concealed
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConcealedPolicy {
+    /// Don't record anything
+    #[default]
+    Skip,
+    /// Record that something was copied, without its content
+    Placeholder,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_no_markers() {
+        let markers = ClipboardMarkers::from_types(["public.utf8-plain-text", "NSStringPboardType", "text/plain", "UTF8_STRING", "TARGETS"]);
+        assert_eq!(markers, ClipboardMarkers::default());
+    }
+
+    #[test]
+    fn test_other_formats() {
+        let markers = ClipboardMarkers::from_types(["public.utf8-plain-text", "public.html"]);
+        assert_eq!(markers, ClipboardMarkers { other_formats: true, ..Default::default() });
+        assert!(!ClipboardMarkers::from_types(["public.utf8-plain-text", TRANSIENT_TYPE]).other_formats);
+    }
+
+    #[test]
+    fn test_macos_markers() {
+        let markers = ClipboardMarkers::from_types(["public.utf8-plain-text", CONCEALED_TYPE, TRANSIENT_TYPE]);
+        assert!(markers.concealed);
+        assert!(markers.transient);
+        assert!(!markers.restored);
+    }
+
+    #[test]
+    fn test_restored_marker() {
+        let markers = ClipboardMarkers::from_types(["public.utf8-plain-text", RESTORED_TYPE]);
+        assert_eq!(markers, ClipboardMarkers { restored: true, ..Default::default() });
+    }
+
+    #[test]
+    fn test_kde_password_manager_hint() {
+        let markers = ClipboardMarkers::from_types(["text/plain", KDE_PASSWORD_MANAGER_HINT]);
+        assert!(markers.concealed);
+        assert!(!markers.transient);
+    }
+}
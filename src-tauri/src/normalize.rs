@@ -0,0 +1,92 @@
+use std::borrow::Cow;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use unicode_normalization::{is_nfc_quick, IsNormalized, UnicodeNormalization};
+
+use crate::db::ClipboardEntry;
+
+/// Converts text to NFC so that decomposed input (e.g. filenames copied from Finder,
+/// which arrive in NFD) compares equal to text typed on the keyboard, and Windows
+/// (`\r\n`) and classic Mac (`\r`) line endings to `\n`.
+/// The stored entry keeps the original text; this is only used for comparisons.
+pub fn normalize_text(text: &str) -> Cow<'_, str> {
+    let text = normalize_line_endings(text);
+    if is_nfc_quick(text.chars()) == IsNormalized::Yes {
+        text
+    } else {
+        Cow::Owned(text.nfc().collect())
+    }
+}
+
+fn normalize_line_endings(text: &str) -> Cow<'_, str> {
+    if text.contains('\r') {
+        Cow::Owned(text.replace("\r\n", "\n").replace('\r', "\n"))
+    } else {
+        Cow::Borrowed(text)
+    }
+}
+
+/// Hashes the entry content for duplicate detection, using the normalized form of text
+pub fn content_hash(entry: &ClipboardEntry) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    if let Some(text) = &entry.text_content {
+        normalize_text(text).hash(&mut hasher);
+    } else if let Some(image_path) = &entry.image_path {
+        image_path.hash(&mut hasher);
+    }
+    hasher.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // "한글" decomposed into jamo, as Finder puts it on the pasteboard
+    const HANGUL_NFD: &str = "\u{1112}\u{1161}\u{11AB}\u{1100}\u{1173}\u{11AF}";
+    const HANGUL_NFC: &str = "한글";
+
+    #[test]
+    fn test_normalize_nfd_to_nfc() {
+        assert_ne!(HANGUL_NFD, HANGUL_NFC);
+        assert_eq!(normalize_text(HANGUL_NFD), HANGUL_NFC);
+    }
+
+    #[test]
+    fn test_normalize_borrows_nfc_text() {
+        assert!(matches!(normalize_text(HANGUL_NFC), Cow::Borrowed(_)));
+        assert!(matches!(normalize_text("plain ascii"), Cow::Borrowed(_)));
+    }
+
+    #[test]
+    fn test_normalize_line_endings() {
+        assert_eq!(normalize_text("a\r\nb\rc\n"), "a\nb\nc\n");
+        assert!(matches!(normalize_text("a\nb"), Cow::Borrowed(_)));
+    }
+
+    #[test]
+    fn test_content_hash_ignores_line_endings() {
+        let windows = ClipboardEntry::new_text_entry("one\r\ntwo".to_string());
+        let unix = ClipboardEntry::new_text_entry("one\ntwo".to_string());
+
+        assert_eq!(content_hash(&windows), content_hash(&unix));
+        assert_eq!(windows.text_content.as_deref(), Some("one\r\ntwo"));
+    }
+
+    #[test]
+    fn test_content_hash_ignores_normalization_form() {
+        let nfd = ClipboardEntry::new_text_entry(HANGUL_NFD.to_string());
+        let nfc = ClipboardEntry::new_text_entry(HANGUL_NFC.to_string());
+
+        assert_eq!(content_hash(&nfd), content_hash(&nfc));
+        // The original bytes are kept for paste-back
+        assert_eq!(nfd.text_content.as_deref(), Some(HANGUL_NFD));
+    }
+
+    #[test]
+    fn test_content_hash_differs_for_different_text() {
+        let a = ClipboardEntry::new_text_entry("a".to_string());
+        let b = ClipboardEntry::new_text_entry("b".to_string());
+
+        assert_ne!(content_hash(&a), content_hash(&b));
+    }
+}
@@ -0,0 +1,49 @@
+use std::collections::HashMap;
+
+use regex::Regex;
+
+/// Regexes written in the settings, compiled once when the settings are applied
+/// rather than for every copy
+#[derive(Debug, Clone, Default)]
+pub struct Patterns {
+    compiled: HashMap<String, Regex>,
+}
+
+impl Patterns {
+    pub fn compile<'a>(patterns: impl IntoIterator<Item = &'a str>) -> Result<Self, String> {
+        let mut compiled = HashMap::new();
+        for pattern in patterns {
+            if !compiled.contains_key(pattern) {
+                let regex = Regex::new(pattern).map_err(|e| format!("Invalid pattern {:?}: {}", pattern, e))?;
+                compiled.insert(pattern.to_string(), regex);
+            }
+        }
+        Ok(Self { compiled })
+    }
+
+    /// The compiled pattern. None for one that isn't in the applied settings, which never matches.
+    pub fn get(&self, pattern: &str) -> Option<&Regex> {
+        self.compiled.get(pattern)
+    }
+
+    /// Whether the pattern is found in the text
+    pub fn is_match(&self, pattern: &str, text: &str) -> bool {
+        self.get(pattern).is_some_and(|regex| regex.is_match(text))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compiled_patterns() {
+        let patterns = Patterns::compile([r"^\d+$", "[a-z]+", r"^\d+$"]).unwrap();
+        assert!(patterns.is_match(r"^\d+$", "42"));
+        assert!(!patterns.is_match(r"^\d+$", "x42"));
+        assert_eq!(patterns.get("[a-z]+").unwrap().find("ABC def").unwrap().as_str(), "def");
+        assert!(!patterns.is_match("other", "other"));
+
+        assert!(Patterns::compile(["("]).is_err());
+    }
+}
@@ -0,0 +1,154 @@
+use chrono::{DateTime, Local, Utc};
+use serde::{Deserialize, Serialize};
+use std::sync::Mutex;
+use std::time::Duration;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Pause {
+    Indefinite,
+    Until(DateTime<Utc>),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PauseStatus {
+    pub paused: bool,
+    /// RFC 3339 time when capture resumes on its own, if paused with a timer
+    pub until: Option<String>,
+}
+
+/// Whether clipboard capture is paused (incognito mode), optionally until a deadline
+#[derive(Default)]
+pub struct PauseState {
+    pause: Mutex<Option<Pause>>,
+}
+
+impl PauseState {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Pauses until `resume` is called
+    pub fn pause(&self) {
+        *self.pause.lock().unwrap() = Some(Pause::Indefinite);
+    }
+
+    /// Pauses for the given duration
+    pub fn pause_for(&self, duration: Duration, now: DateTime<Utc>) {
+        // A duration too long to represent is as good as no deadline
+        let until = chrono::Duration::from_std(duration).ok().and_then(|d| now.checked_add_signed(d));
+        *self.pause.lock().unwrap() = Some(until.map_or(Pause::Indefinite, Pause::Until));
+    }
+
+    pub fn resume(&self) {
+        *self.pause.lock().unwrap() = None;
+    }
+
+    /// Takes over the status of another process, the one capturing the clipboard
+    pub fn mirror(&self, status: &PauseStatus) {
+        let until = status.until.as_deref().and_then(|until| DateTime::parse_from_rfc3339(until).ok());
+        *self.pause.lock().unwrap() = match until {
+            _ if !status.paused => None,
+            Some(until) => Some(Pause::Until(until.with_timezone(&Utc))),
+            None => Some(Pause::Indefinite),
+        };
+    }
+
+    pub fn is_paused(&self, now: DateTime<Utc>) -> bool {
+        self.status(now).paused
+    }
+
+    pub fn status(&self, now: DateTime<Utc>) -> PauseStatus {
+        match *self.pause.lock().unwrap() {
+            Some(Pause::Indefinite) => PauseStatus { paused: true, until: None },
+            Some(Pause::Until(until)) if until > now => PauseStatus { paused: true, until: Some(until.to_rfc3339()) },
+            _ => PauseStatus { paused: false, until: None },
+        }
+    }
+
+    /// Clears a timed pause whose deadline has passed.
+    /// Returns true only on the call that ends it, so callers can announce the resume once.
+    pub fn expire(&self, now: DateTime<Utc>) -> bool {
+        let mut pause = self.pause.lock().unwrap();
+        match *pause {
+            Some(Pause::Until(until)) if until <= now => {
+                *pause = None;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Label for the tray menu item that toggles the pause
+    pub fn menu_label(&self, now: DateTime<Utc>) -> String {
+        match *self.pause.lock().unwrap() {
+            Some(Pause::Indefinite) => "Resume Capture (paused)".to_string(),
+            Some(Pause::Until(until)) if until > now => {
+                format!("Resume Capture (paused until {})", until.with_timezone(&Local).format("%H:%M"))
+            }
+            _ => "Pause Capture".to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pause_and_resume() {
+        let state = PauseState::new();
+        let now = Utc::now();
+        assert!(!state.is_paused(now));
+
+        state.pause();
+        assert_eq!(state.status(now), PauseStatus { paused: true, until: None });
+        assert_eq!(state.menu_label(now), "Resume Capture (paused)");
+
+        state.resume();
+        assert!(!state.is_paused(now));
+        assert_eq!(state.menu_label(now), "Pause Capture");
+    }
+
+    #[test]
+    fn test_timed_pause_expires() {
+        let state = PauseState::new();
+        let now = Utc::now();
+
+        state.pause_for(Duration::from_secs(15 * 60), now);
+        assert!(state.is_paused(now));
+        assert!(state.status(now).until.is_some());
+        assert!(!state.expire(now));
+
+        let later = now + chrono::Duration::minutes(15);
+        assert!(!state.is_paused(later));
+        assert!(state.expire(later));
+        // The resume is only reported once
+        assert!(!state.expire(later));
+    }
+
+    #[test]
+    fn test_mirror_status() {
+        let now = Utc::now();
+        let capturing = PauseState::new();
+        let following = PauseState::new();
+
+        capturing.pause_for(Duration::from_secs(5 * 60), now);
+        following.mirror(&capturing.status(now));
+        assert_eq!(following.status(now), capturing.status(now));
+
+        following.mirror(&PauseStatus { paused: true, until: None });
+        assert_eq!(following.status(now), PauseStatus { paused: true, until: None });
+        following.mirror(&PauseStatus { paused: false, until: None });
+        assert!(!following.is_paused(now));
+    }
+
+    #[test]
+    fn test_indefinite_pause_never_expires() {
+        let state = PauseState::new();
+        state.pause();
+
+        let much_later = Utc::now() + chrono::Duration::days(365);
+        assert!(!state.expire(much_later));
+        assert!(state.is_paused(much_later));
+    }
+}
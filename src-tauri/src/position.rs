@@ -0,0 +1,95 @@
+use serde::{Deserialize, Serialize};
+
+/// Where the history window appears when it's opened
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WindowPosition {
+    /// Centered on the screen
+    #[default]
+    Center,
+    /// Below the tray icon, or above it when the tray sits at the bottom of the screen
+    TrayIcon,
+    /// With its top-left corner at the mouse cursor
+    Cursor,
+}
+
+/// Rectangle in physical pixels
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Bounds {
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+}
+
+impl Bounds {
+    pub fn contains(&self, x: f64, y: f64) -> bool {
+        x >= self.x && x < self.x + self.width && y >= self.y && y < self.y + self.height
+    }
+}
+
+/// Top-left corner for a window of the given size, centered under the tray icon
+pub fn below_tray_icon(tray: Bounds, width: f64, height: f64, monitor: Bounds) -> (f64, f64) {
+    let x = tray.x + tray.width / 2.0 - width / 2.0;
+    let below = tray.y + tray.height;
+    // Taskbars at the bottom of the screen leave no room below the icon
+    let y = if below + height > monitor.y + monitor.height { tray.y - height } else { below };
+    clamp_to_monitor(x, y, width, height, monitor)
+}
+
+/// Top-left corner for a window of the given size at the cursor
+pub fn at_cursor(cursor_x: f64, cursor_y: f64, width: f64, height: f64, monitor: Bounds) -> (f64, f64) {
+    clamp_to_monitor(cursor_x, cursor_y, width, height, monitor)
+}
+
+/// Moves the window so it lies entirely on the monitor, keeping its top-left corner
+/// visible if it's larger than the monitor
+pub fn clamp_to_monitor(x: f64, y: f64, width: f64, height: f64, monitor: Bounds) -> (f64, f64) {
+    let x = x.min(monitor.x + monitor.width - width).max(monitor.x);
+    let y = y.min(monitor.y + monitor.height - height).max(monitor.y);
+    (x, y)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MONITOR: Bounds = Bounds { x: 0.0, y: 0.0, width: 1920.0, height: 1080.0 };
+
+    #[test]
+    fn test_below_tray_icon() {
+        let tray = Bounds { x: 1500.0, y: 0.0, width: 40.0, height: 24.0 };
+        assert_eq!(below_tray_icon(tray, 400.0, 600.0, MONITOR), (1320.0, 24.0));
+    }
+
+    #[test]
+    fn test_above_tray_icon_at_bottom() {
+        let tray = Bounds { x: 1500.0, y: 1040.0, width: 40.0, height: 40.0 };
+        assert_eq!(below_tray_icon(tray, 400.0, 600.0, MONITOR), (1320.0, 440.0));
+    }
+
+    #[test]
+    fn test_tray_icon_near_edge_is_clamped() {
+        let tray = Bounds { x: 1890.0, y: 0.0, width: 30.0, height: 24.0 };
+        assert_eq!(below_tray_icon(tray, 400.0, 600.0, MONITOR), (1520.0, 24.0));
+    }
+
+    #[test]
+    fn test_cursor_on_second_monitor() {
+        let monitor = Bounds { x: 1920.0, y: 0.0, width: 1280.0, height: 800.0 };
+        assert_eq!(at_cursor(2000.0, 100.0, 400.0, 600.0, monitor), (2000.0, 100.0));
+        assert_eq!(at_cursor(3100.0, 700.0, 400.0, 600.0, monitor), (2800.0, 200.0));
+    }
+
+    #[test]
+    fn test_window_larger_than_monitor() {
+        let (x, y) = clamp_to_monitor(500.0, 500.0, 3000.0, 2000.0, MONITOR);
+        assert_eq!((x, y), (0.0, 0.0));
+    }
+
+    #[test]
+    fn test_contains() {
+        assert!(MONITOR.contains(0.0, 0.0));
+        assert!(!MONITOR.contains(1920.0, 10.0));
+    }
+}
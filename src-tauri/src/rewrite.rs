@@ -0,0 +1,248 @@
+use std::sync::{Arc, LazyLock};
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::app_filter::SourceApp;
+use crate::capture::Capture;
+use crate::patterns::Patterns;
+use crate::pipeline::{ClipboardBackend, ClipboardCopy, Flow, Stage};
+
+// Query parameters added for click tracking, removed by RemoveTrackingParams besides utm_*
+const TRACKING_PARAMS: &[&str] = &["fbclid", "gclid", "mc_eid"];
+// URL with a query string, which is group 1
+static URL_WITH_QUERY: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"https?://[^\s?#]*\?([^\s#]*)").unwrap());
+
+/// How a rule rewrites the copied text
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RewriteAction {
+    /// Regex replacement; `with` may refer to groups as `$1` or `${name}`
+    Replace { find: String, with: String },
+    /// Removes line breaks at the end, e.g. the one spreadsheets add to a copied cell
+    StripTrailingNewline,
+    /// Removes utm_* and other tracking parameters from URLs
+    RemoveTrackingParams,
+    /// Converts curly quotes to straight ones
+    StraightQuotes,
+}
+
+/// Rewrites matching text copies, on the clipboard as well as in the history
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RewriteRule {
+    pub name: String,
+    /// Regex the copied text must contain; None matches any text
+    #[serde(default)]
+    pub pattern: Option<String>,
+    /// Bundle id / WM_CLASS or name of the application the copy must come from; None matches any
+    #[serde(default)]
+    pub app: Option<String>,
+    pub action: RewriteAction,
+}
+
+impl RewriteRule {
+    pub fn validate(&self) -> Result<(), String> {
+        if let Some(pattern) = &self.pattern {
+            Regex::new(pattern).map_err(|e| format!("Invalid pattern for rule {:?}: {}", self.name, e))?;
+        }
+        if let RewriteAction::Replace { find, .. } = &self.action {
+            Regex::new(find).map_err(|e| format!("Invalid replacement for rule {:?}: {}", self.name, e))?;
+        }
+        Ok(())
+    }
+
+    /// Regexes of the rule, compiled into the Patterns the rule is applied with
+    pub fn patterns(&self) -> impl Iterator<Item = &str> {
+        let find = match &self.action {
+            RewriteAction::Replace { find, .. } => Some(find.as_str()),
+            _ => None,
+        };
+        self.pattern.as_deref().into_iter().chain(find)
+    }
+
+    fn matches(&self, text: &str, source_app: Option<&SourceApp>, patterns: &Patterns) -> bool {
+        if let Some(app) = &self.app {
+            if !source_app.is_some_and(|source_app| source_app.is(app)) {
+                return false;
+            }
+        }
+        self.pattern.as_ref().is_none_or(|pattern| patterns.is_match(pattern, text))
+    }
+
+    fn rewrite(&self, text: &str, patterns: &Patterns) -> String {
+        match &self.action {
+            RewriteAction::Replace { find, with } => match patterns.get(find) {
+                Some(regex) => regex.replace_all(text, with.as_str()).into_owned(),
+                None => text.to_string(),
+            },
+            RewriteAction::StripTrailingNewline => text.trim_end_matches(['\n', '\r']).to_string(),
+            RewriteAction::RemoveTrackingParams => remove_tracking_params(text),
+            RewriteAction::StraightQuotes => straight_quotes(text),
+        }
+    }
+}
+
+fn is_tracking_param(param: &str) -> bool {
+    let key = param.split('=').next().unwrap_or_default();
+    key.starts_with("utm_") || TRACKING_PARAMS.contains(&key)
+}
+
+fn remove_tracking_params(text: &str) -> String {
+    URL_WITH_QUERY.replace_all(text, |captures: &regex::Captures| {
+        let whole = &captures[0];
+        let query = &captures[1];
+        let base = &whole[..whole.len() - query.len() - 1];
+        let kept: Vec<&str> = query.split('&').filter(|param| !param.is_empty() && !is_tracking_param(param)).collect();
+        if kept.is_empty() {
+            base.to_string()
+        } else {
+            format!("{}?{}", base, kept.join("&"))
+        }
+    })
+    .into_owned()
+}
+
+fn straight_quotes(text: &str) -> String {
+    text.chars()
+        .map(|c| match c {
+            '\u{2018}' | '\u{2019}' | '\u{201A}' | '\u{201B}' => '\'',
+            '\u{201C}' | '\u{201D}' | '\u{201E}' | '\u{201F}' => '"',
+            c => c,
+        })
+        .collect()
+}
+
+/// Applies the matching rules in order, each to the result of the previous one.
+/// Returns the new text if any rule changed it.
+pub fn apply_rules(rules: &[RewriteRule], patterns: &Patterns, text: &str, source_app: Option<&SourceApp>) -> Option<String> {
+    let mut current = text.to_string();
+    for rule in rules {
+        if rule.matches(&current, source_app, patterns) {
+            current = rule.rewrite(&current, patterns);
+        }
+    }
+    (current != text).then_some(current)
+}
+
+/// Pipeline stage that rewrites plain text copies with the configured rules and puts
+/// the result back on the clipboard, so the rewritten text is what gets pasted
+pub struct RewriteRules {
+    pub backend: Arc<dyn ClipboardBackend>,
+    // Change count of the text this stage put on the clipboard, which shows up as a new copy
+    written: Option<isize>,
+}
+
+impl RewriteRules {
+    pub fn new(backend: Arc<dyn ClipboardBackend>) -> Self {
+        Self { backend, written: None }
+    }
+}
+
+impl Stage for RewriteRules {
+    fn name(&self) -> &'static str {
+        "rewrite rules"
+    }
+
+    fn process(&mut self, copy: &mut ClipboardCopy, capture: &Capture) -> Flow {
+        if self.written.take() == Some(copy.change_count) {
+            return Flow::Stop("rewritten copy, already stored".to_string());
+        }
+        // Concealed copies only carry a placeholder, which must not replace the real content
+        if copy.markers.concealed {
+            return Flow::Continue;
+        }
+        let Some(entry) = copy.entry.as_mut() else {
+            return Flow::Continue;
+        };
+        let Some(text) = entry.text_content.as_deref() else {
+            return Flow::Continue;
+        };
+
+        let rules = capture.settings.read().unwrap().rewrite_rules.clone();
+        let patterns = capture.patterns.read().unwrap();
+        let Some(rewritten) = apply_rules(&rules, &patterns, text, copy.source_app.as_ref()) else {
+            return Flow::Continue;
+        };
+        // Writing the text back would drop the other formats, so such copies are kept as they are
+        if copy.markers.other_formats {
+            println!("[PIPELINE] Not rewriting copy that has more than plain text");
+            return Flow::Continue;
+        }
+        match self.backend.set_text(&rewritten) {
+            Ok(change_count) => {
+                println!("[PIPELINE] Rewrote copy on the clipboard");
+                self.written = Some(change_count);
+                entry.text_content = Some(rewritten);
+            }
+            // The original is still on the clipboard, so it's also what gets stored
+            Err(e) => println!("[PIPELINE] Failed to write rewritten copy to the clipboard: {}", e),
+        }
+        Flow::Continue
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(action: RewriteAction) -> RewriteRule {
+        RewriteRule { name: "test".to_string(), pattern: None, app: None, action }
+    }
+
+    // Applies the rules with their patterns compiled, as the settings do
+    fn apply(rules: &[RewriteRule], text: &str, source_app: Option<&SourceApp>) -> Option<String> {
+        let patterns = Patterns::compile(rules.iter().flat_map(RewriteRule::patterns)).unwrap();
+        apply_rules(rules, &patterns, text, source_app)
+    }
+
+    fn app(name: &str) -> SourceApp {
+        SourceApp { id: None, name: Some(name.to_string()), window_title: None }
+    }
+
+    #[test]
+    fn test_named_transforms() {
+        let strip = [rule(RewriteAction::StripTrailingNewline)];
+        assert_eq!(apply(&strip, "42\r\n", None), Some("42".to_string()));
+        assert_eq!(apply(&strip, "42", None), None);
+
+        let quotes = [rule(RewriteAction::StraightQuotes)];
+        assert_eq!(apply(&quotes, "“It’s”", None), Some("\"It's\"".to_string()));
+
+        let tracking = [rule(RewriteAction::RemoveTrackingParams)];
+        assert_eq!(
+            apply(&tracking, "see https://example.com/a?id=3&utm_source=x&utm_medium=y#top now", None),
+            Some("see https://example.com/a?id=3#top now".to_string())
+        );
+        assert_eq!(
+            apply(&tracking, "https://example.com/?utm_campaign=z&fbclid=abc", None),
+            Some("https://example.com/".to_string())
+        );
+        assert_eq!(apply(&tracking, "https://example.com/?q=utm_source", None), None);
+    }
+
+    #[test]
+    fn test_rules_match_on_app_and_content() {
+        let excel = [RewriteRule { app: Some("microsoft excel".to_string()), ..rule(RewriteAction::StripTrailingNewline) }];
+        assert_eq!(apply(&excel, "1\n", Some(&app("Microsoft Excel"))), Some("1".to_string()));
+        assert_eq!(apply(&excel, "1\n", Some(&app("Terminal"))), None);
+        assert_eq!(apply(&excel, "1\n", None), None);
+
+        let tickets = [RewriteRule {
+            pattern: Some(r"^[A-Z]+-\d+$".to_string()),
+            ..rule(RewriteAction::Replace { find: r"^(.*)$".to_string(), with: "https://jira.example.com/browse/$1".to_string() })
+        }];
+        assert_eq!(apply(&tickets, "PROJ-7", None), Some("https://jira.example.com/browse/PROJ-7".to_string()));
+        assert_eq!(apply(&tickets, "not a ticket", None), None);
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(rule(RewriteAction::StraightQuotes).validate().is_ok());
+        assert!(RewriteRule { pattern: Some("(".to_string()), ..rule(RewriteAction::StraightQuotes) }.validate().is_err());
+        assert!(rule(RewriteAction::Replace { find: "[".to_string(), with: String::new() }).validate().is_err());
+
+        let parsed: RewriteRule =
+            serde_json::from_str(r#"{"name": "quotes", "action": "straight_quotes"}"#).unwrap();
+        assert_eq!(parsed, RewriteRule { name: "quotes".to_string(), ..rule(RewriteAction::StraightQuotes) });
+    }
+}
@@ -0,0 +1,299 @@
+use serde::Serialize;
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::thread;
+
+use crate::db::ClipboardEntry;
+use crate::fzf::calculate_match_score;
+use crate::normalize::normalize_text;
+
+// How many candidates a worker scores before checking for a newer search
+const CANCEL_CHECK_INTERVAL: usize = 64;
+// Below this many candidates per worker, spawning threads costs more than it saves
+const MIN_CHUNK_SIZE: usize = 256;
+// Only the start of longer entries is searched, so one huge copy can't slow down every search
+const MAX_INDEXED_CHARS: usize = 10_000;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SearchHit {
+    pub id: i64,
+    pub score: i32,
+}
+
+// Matches of the previous query, reused when the next query extends it
+struct LastSearch {
+    query: String,
+    index_version: u64,
+    ids: Vec<i64>,
+}
+
+/// In-memory fuzzy search over the text entries of the clipboard history
+pub struct SearchEngine {
+    index: RwLock<HashMap<i64, Arc<String>>>,
+    index_version: AtomicU64,
+    last_search: Mutex<Option<LastSearch>>,
+    generation: AtomicU64,
+}
+
+impl SearchEngine {
+    pub fn new() -> Self {
+        Self {
+            index: RwLock::new(HashMap::new()),
+            index_version: AtomicU64::new(0),
+            last_search: Mutex::new(None),
+            generation: AtomicU64::new(0),
+        }
+    }
+
+    /// Builds the index from entries already stored in the database
+    pub fn from_entries(entries: &[ClipboardEntry]) -> Self {
+        let engine = Self::new();
+        for entry in entries {
+            engine.insert_entry(entry);
+        }
+        engine
+    }
+
+    /// Replaces the whole index with the (id, text) pairs of
+    /// ClipboardDatabase::get_text_contents, e.g. once an encrypted database is unlocked
+    pub fn reset(&self, texts: &[(i64, String)]) {
+        self.index.write().unwrap().clear();
+        for (id, text) in texts {
+            self.insert_text(*id, text);
+        }
+    }
+
+    /// Adds a text entry to the index. Image entries are ignored.
+    pub fn insert_entry(&self, entry: &ClipboardEntry) {
+        if let (Some(id), Some(text)) = (entry.id, entry.text_content.as_ref()) {
+            self.insert_text(id, text);
+        }
+    }
+
+    /// Indexes the text in NFC so searches compare like with like
+    pub fn insert_text(&self, id: i64, text: &str) {
+        let end = text.char_indices().nth(MAX_INDEXED_CHARS).map_or(text.len(), |(i, _)| i);
+        let text = normalize_text(&text[..end]).into_owned();
+        self.index.write().unwrap().insert(id, Arc::new(text));
+        // New entries may match the cached query, so it can no longer narrow the next search
+        self.index_version.fetch_add(1, Ordering::SeqCst);
+    }
+
+    /// Removes an entry from the index
+    pub fn remove_entry(&self, id: i64) {
+        self.index.write().unwrap().remove(&id);
+    }
+
+    /// Scores every indexed entry against the query, best matches first.
+    /// Returns None if a newer search started before this one finished.
+    pub fn search(&self, query: &str) -> Option<Vec<SearchHit>> {
+        let generation = self.generation.fetch_add(1, Ordering::SeqCst) + 1;
+        let index_version = self.index_version.load(Ordering::SeqCst);
+
+        if query.is_empty() {
+            let mut hits: Vec<SearchHit> = self.index.read().unwrap()
+                .keys()
+                .map(|&id| SearchHit { id, score: 0 })
+                .collect();
+            hits.sort_by_key(|hit| Reverse(hit.id));
+            return Some(hits);
+        }
+
+        let query = normalize_text(query).into_owned();
+        let candidates = self.collect_candidates(&query, index_version);
+        let mut hits = self.score_candidates(&candidates, &query, generation)?;
+
+        *self.last_search.lock().unwrap() = Some(LastSearch {
+            query,
+            index_version,
+            ids: hits.iter().map(|hit| hit.id).collect(),
+        });
+
+        // Best score first, most recent first on ties
+        hits.sort_by(|a, b| b.score.cmp(&a.score).then(b.id.cmp(&a.id)));
+        Some(hits)
+    }
+
+    fn collect_candidates(&self, query: &str, index_version: u64) -> Vec<(i64, Arc<String>)> {
+        let index = self.index.read().unwrap();
+        let last_search = self.last_search.lock().unwrap();
+
+        match last_search.as_ref() {
+            // A query that extends the previous one can only match a subset of its matches
+            Some(last) if last.index_version == index_version && query.starts_with(&last.query) => last.ids
+                .iter()
+                .filter_map(|id| index.get(id).map(|text| (*id, text.clone())))
+                .collect(),
+            _ => index.iter().map(|(id, text)| (*id, text.clone())).collect(),
+        }
+    }
+
+    fn score_candidates(&self, candidates: &[(i64, Arc<String>)], query: &str, generation: u64) -> Option<Vec<SearchHit>> {
+        let workers = thread::available_parallelism().map(|n| n.get()).unwrap_or(1);
+        let chunk_size = candidates.len().div_ceil(workers).max(MIN_CHUNK_SIZE);
+
+        let hits = thread::scope(|scope| {
+            let handles: Vec<_> = candidates
+                .chunks(chunk_size)
+                .map(|chunk| scope.spawn(move || self.score_chunk(chunk, query, generation)))
+                .collect();
+
+            let mut hits = Vec::new();
+            for handle in handles {
+                hits.extend(handle.join().ok()??);
+            }
+            Some(hits)
+        })?;
+
+        if self.is_stale(generation) {
+            return None;
+        }
+        Some(hits)
+    }
+
+    fn score_chunk(&self, chunk: &[(i64, Arc<String>)], query: &str, generation: u64) -> Option<Vec<SearchHit>> {
+        let mut hits = Vec::new();
+        for (i, (id, text)) in chunk.iter().enumerate() {
+            if i % CANCEL_CHECK_INTERVAL == 0 && self.is_stale(generation) {
+                return None;
+            }
+            if let Some(score) = calculate_match_score(text, query) {
+                hits.push(SearchHit { id: *id, score });
+            }
+        }
+        Some(hits)
+    }
+
+    fn is_stale(&self, generation: u64) -> bool {
+        self.generation.load(Ordering::SeqCst) != generation
+    }
+}
+
+impl Default for SearchEngine {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text_entry(id: i64, text: &str) -> ClipboardEntry {
+        let mut entry = ClipboardEntry::new_text_entry(text.to_string());
+        entry.id = Some(id);
+        entry
+    }
+
+    fn hit_ids(hits: &[SearchHit]) -> Vec<i64> {
+        hits.iter().map(|hit| hit.id).collect()
+    }
+
+    #[test]
+    fn test_search_ranks_matches() {
+        let engine = SearchEngine::from_entries(&[
+            text_entry(1, "hello_world"),
+            text_entry(2, "helloworld"),
+            text_entry(3, "goodbye"),
+        ]);
+
+        let hits = engine.search("hw").unwrap();
+        assert_eq!(hit_ids(&hits), vec![1, 2]);
+        assert!(hits[0].score > hits[1].score);
+    }
+
+    #[test]
+    fn test_empty_query_returns_all_by_recency() {
+        let engine = SearchEngine::from_entries(&[text_entry(1, "a"), text_entry(2, "b")]);
+
+        let hits = engine.search("").unwrap();
+        assert_eq!(hit_ids(&hits), vec![2, 1]);
+    }
+
+    #[test]
+    fn test_image_entries_are_not_indexed() {
+        let mut image = ClipboardEntry::new_image_entry("data:image/png;base64,".to_string());
+        image.id = Some(1);
+        let engine = SearchEngine::from_entries(&[image]);
+
+        assert!(engine.search("").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_search_normalizes_unicode() {
+        // "한글.txt" with the Hangul decomposed, as copied from a Finder path
+        let engine = SearchEngine::from_entries(&[text_entry(1, "\u{1112}\u{1161}\u{11AB}\u{1100}\u{1173}\u{11AF}.txt")]);
+
+        assert_eq!(hit_ids(&engine.search("한글").unwrap()), vec![1]);
+        assert_eq!(hit_ids(&engine.search("\u{1112}\u{1161}\u{11AB}").unwrap()), vec![1]);
+    }
+
+    #[test]
+    fn test_extended_query_narrows_previous_matches() {
+        let engine = SearchEngine::from_entries(&[text_entry(1, "apple"), text_entry(2, "apricot")]);
+
+        assert_eq!(engine.search("ap").unwrap().len(), 2);
+        assert_eq!(hit_ids(&engine.search("app").unwrap()), vec![1]);
+        // Shortening the query searches the whole index again
+        assert_eq!(engine.search("ap").unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_insert_invalidates_narrowing() {
+        let engine = SearchEngine::from_entries(&[text_entry(1, "apple")]);
+
+        assert_eq!(engine.search("ap").unwrap().len(), 1);
+        engine.insert_entry(&text_entry(2, "apricot"));
+        assert_eq!(hit_ids(&engine.search("apr").unwrap()), vec![2]);
+    }
+
+    #[test]
+    fn test_reset_replaces_index() {
+        let engine = SearchEngine::from_entries(&[text_entry(1, "apple")]);
+
+        engine.reset(&[(2, "apricot".to_string())]);
+        assert_eq!(hit_ids(&engine.search("ap").unwrap()), vec![2]);
+    }
+
+    #[test]
+    fn test_remove_entry() {
+        let engine = SearchEngine::from_entries(&[text_entry(1, "apple"), text_entry(2, "apricot")]);
+
+        assert_eq!(engine.search("a").unwrap().len(), 2);
+        engine.remove_entry(2);
+        assert_eq!(hit_ids(&engine.search("ap").unwrap()), vec![1]);
+    }
+
+    #[test]
+    fn test_only_the_start_of_long_entries_is_searched() {
+        let long = format!("{}needle", "한".repeat(MAX_INDEXED_CHARS));
+        let engine = SearchEngine::from_entries(&[text_entry(1, "needle first"), text_entry(2, &long)]);
+
+        assert_eq!(hit_ids(&engine.search("needle").unwrap()), vec![1]);
+        assert_eq!(engine.search("한한").unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_parallel_search_over_large_index() {
+        let entries: Vec<ClipboardEntry> = (1..=5000)
+            .map(|id| text_entry(id, &format!("entry number {}", id)))
+            .collect();
+        let engine = SearchEngine::from_entries(&entries);
+
+        let hits = engine.search("4999").unwrap();
+        assert!(hit_ids(&hits).contains(&4999));
+        assert_eq!(engine.search("entry").unwrap().len(), 5000);
+    }
+
+    #[test]
+    fn test_stale_search_is_cancelled() {
+        let engine = SearchEngine::from_entries(&[text_entry(1, "apple")]);
+        let candidates = engine.collect_candidates("a", 1);
+
+        // A newer search has started since generation 1
+        engine.generation.store(2, Ordering::SeqCst);
+        assert_eq!(engine.score_candidates(&candidates, "a", 1), None);
+    }
+}
@@ -0,0 +1,275 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::app_filter::AppFilter;
+use crate::hooks::Hook;
+use crate::http_api::HttpApiSettings;
+use crate::markers::ConcealedPolicy;
+use crate::patterns::Patterns;
+use crate::position::WindowPosition;
+use crate::rewrite::RewriteRule;
+use crate::secrets::{SecretAction, SecretDetector};
+use crate::webhook::WebhookSettings;
+
+pub const SETTINGS_FILE_NAME: &str = "settings.json";
+
+const DEFAULT_POLL_INTERVAL_MS: u64 = 1000;
+const MIN_POLL_INTERVAL_MS: u64 = 100;
+const MAX_POLL_INTERVAL_MS: u64 = 60_000;
+const DEFAULT_HOTKEY: &str = "CmdOrCtrl+Shift+V";
+const DEFAULT_TRAY_RECENT_COUNT: usize = 10;
+const MAX_TRAY_RECENT_COUNT: usize = 50;
+
+/// User settings, stored as JSON in the app config directory.
+/// Missing fields take their defaults, so older files keep loading.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    /// How often the clipboard is checked for changes
+    pub poll_interval_ms: u64,
+    /// Oldest entries beyond this count are deleted; None keeps everything
+    pub max_entries: Option<usize>,
+    /// Entries older than this many days are deleted; None keeps everything
+    pub max_age_days: Option<u32>,
+    /// Copies larger than this are not recorded; None records any size
+    pub max_entry_kb: Option<usize>,
+    pub app_filter: AppFilter,
+    pub concealed_policy: ConcealedPolicy,
+    /// Action overrides for the built-in secret rules, by rule name
+    pub secret_actions: HashMap<String, SecretAction>,
+    /// Global shortcut that toggles the history window, e.g. "CmdOrCtrl+Shift+V"; None disables it
+    pub hotkey: Option<String>,
+    pub window_position: WindowPosition,
+    /// Number of recent entries listed in the tray menu, besides the pinned ones
+    pub tray_recent_count: usize,
+    pub http_api: HttpApiSettings,
+    /// Commands run for matching copies, in order
+    pub hooks: Vec<Hook>,
+    pub webhook: WebhookSettings,
+    /// Rewrites applied to text copies, in order
+    pub rewrite_rules: Vec<RewriteRule>,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            poll_interval_ms: DEFAULT_POLL_INTERVAL_MS,
+            max_entries: None,
+            max_age_days: None,
+            max_entry_kb: None,
+            app_filter: AppFilter::default(),
+            concealed_policy: ConcealedPolicy::default(),
+            secret_actions: HashMap::new(),
+            hotkey: Some(DEFAULT_HOTKEY.to_string()),
+            window_position: WindowPosition::default(),
+            tray_recent_count: DEFAULT_TRAY_RECENT_COUNT,
+            http_api: HttpApiSettings::default(),
+            hooks: Vec::new(),
+            webhook: WebhookSettings::default(),
+            rewrite_rules: Vec::new(),
+        }
+    }
+}
+
+impl Settings {
+    /// Reads the settings file, falling back to the defaults if it doesn't exist yet
+    pub fn load(path: &Path) -> Result<Self, String> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let contents = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+        let settings: Self = serde_json::from_str(&contents).map_err(|e| e.to_string())?;
+        settings.validate()?;
+        Ok(settings)
+    }
+
+    /// Loads the settings, falling back to defaults when the file can't be used.
+    /// An unusable file is first moved aside to `<name>.invalid` so saving the
+    /// defaults can't overwrite it. Returns why saving must be refused if that fails.
+    pub fn load_or_default(path: &Path) -> (Self, Option<String>) {
+        let error = match Self::load(path) {
+            Ok(settings) => return (settings, None),
+            Err(e) => e,
+        };
+        let backup = path.with_extension("json.invalid");
+        match std::fs::rename(path, &backup) {
+            Ok(()) => {
+                println!("Failed to load settings, using defaults. The old file was moved to {:?}: {}", backup, error);
+                (Self::default(), None)
+            }
+            Err(e) => {
+                println!("Failed to load settings or move them aside, using defaults: {}: {}", error, e);
+                let refusal = format!("Settings file {:?} could not be read ({}), fix or remove it and restart", path, error);
+                (Self::default(), Some(refusal))
+            }
+        }
+    }
+
+    /// Writes the settings file, replacing the old one only once the new one is complete
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+        }
+        let contents = serde_json::to_string_pretty(self).map_err(|e| e.to_string())?;
+        let temp_path = path.with_extension("json.tmp");
+        std::fs::write(&temp_path, contents).map_err(|e| e.to_string())?;
+        std::fs::rename(&temp_path, path).map_err(|e| e.to_string())
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if !(MIN_POLL_INTERVAL_MS..=MAX_POLL_INTERVAL_MS).contains(&self.poll_interval_ms) {
+            return Err(format!(
+                "Poll interval must be between {} and {} ms",
+                MIN_POLL_INTERVAL_MS, MAX_POLL_INTERVAL_MS
+            ));
+        }
+        if self.max_entries == Some(0) {
+            return Err("History limit must be at least 1 entry".to_string());
+        }
+        if self.max_age_days == Some(0) {
+            return Err("History age limit must be at least 1 day".to_string());
+        }
+        if self.max_entry_kb == Some(0) {
+            return Err("Entry size limit must be at least 1 KB".to_string());
+        }
+        if self.tray_recent_count > MAX_TRAY_RECENT_COUNT {
+            return Err(format!("The tray menu can list at most {} recent entries", MAX_TRAY_RECENT_COUNT));
+        }
+        if self.hotkey.as_ref().is_some_and(|hotkey| hotkey.trim().is_empty()) {
+            return Err("Hotkey must not be empty; clear it to disable the shortcut".to_string());
+        }
+        if self.secret_actions.values().any(|action| *action == SecretAction::Expire(0)) {
+            return Err("Secret expiry must be at least 1 second".to_string());
+        }
+        self.http_api.validate()?;
+        for hook in &self.hooks {
+            hook.validate()?;
+        }
+        self.webhook.validate()?;
+        for rule in &self.rewrite_rules {
+            rule.validate()?;
+        }
+        self.secret_detector().map(|_| ())
+    }
+
+    /// Secret detector with the built-in rules and this file's overrides
+    pub fn secret_detector(&self) -> Result<SecretDetector, String> {
+        let mut detector = SecretDetector::new();
+        let mut unknown = detector.set_actions(&self.secret_actions);
+        if !unknown.is_empty() {
+            unknown.sort();
+            return Err(format!("Unknown secret rules: {}", unknown.join(", ")));
+        }
+        Ok(detector)
+    }
+
+    /// Regexes of the hooks, the webhook and the rewrite rules, compiled
+    pub fn patterns(&self) -> Result<Patterns, String> {
+        let hooks = self.hooks.iter().filter_map(|hook| hook.pattern.as_deref());
+        let rules = self.rewrite_rules.iter().flat_map(RewriteRule::patterns);
+        Patterns::compile(hooks.chain(self.webhook.pattern.as_deref()).chain(rules))
+    }
+
+    pub fn poll_interval(&self) -> std::time::Duration {
+        std::time::Duration::from_millis(self.poll_interval_ms)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app_filter::AppFilterMode;
+
+    fn temp_path(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("clipboard_settings_{}_{}.json", name, std::process::id()))
+    }
+
+    #[test]
+    fn test_missing_file_gives_defaults() {
+        let path = temp_path("missing");
+        let _ = std::fs::remove_file(&path);
+        assert_eq!(Settings::load(&path).unwrap(), Settings::default());
+    }
+
+    #[test]
+    fn test_invalid_file_is_moved_aside() {
+        let path = temp_path("invalid");
+        let backup = path.with_extension("json.invalid");
+        std::fs::write(&path, "{ not json").unwrap();
+
+        let (settings, refusal) = Settings::load_or_default(&path);
+        assert_eq!(settings, Settings::default());
+        assert_eq!(refusal, None);
+        assert!(!path.exists());
+        assert_eq!(std::fs::read_to_string(&backup).unwrap(), "{ not json");
+
+        let _ = std::fs::remove_file(&backup);
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let path = temp_path("round_trip");
+        let settings = Settings {
+            poll_interval_ms: 500,
+            max_entries: Some(1000),
+            app_filter: AppFilter { mode: AppFilterMode::Deny, apps: vec!["com.apple.Terminal".to_string()] },
+            concealed_policy: ConcealedPolicy::Placeholder,
+            secret_actions: HashMap::from([("jwt".to_string(), SecretAction::Skip)]),
+            hotkey: None,
+            window_position: WindowPosition::TrayIcon,
+            ..Settings::default()
+        };
+
+        settings.save(&path).unwrap();
+        assert_eq!(Settings::load(&path).unwrap(), settings);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_partial_file_uses_defaults() {
+        let path = temp_path("partial");
+        std::fs::write(&path, r#"{ "max_age_days": 30 }"#).unwrap();
+
+        let settings = Settings::load(&path).unwrap();
+        assert_eq!(settings.max_age_days, Some(30));
+        assert_eq!(settings.poll_interval_ms, DEFAULT_POLL_INTERVAL_MS);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_validation() {
+        assert!(Settings::default().validate().is_ok());
+
+        let settings = Settings { poll_interval_ms: 10, ..Settings::default() };
+        assert!(settings.validate().is_err());
+
+        let settings = Settings { max_entries: Some(0), ..Settings::default() };
+        assert!(settings.validate().is_err());
+
+        let settings = Settings { hotkey: Some(" ".to_string()), ..Settings::default() };
+        assert!(settings.validate().is_err());
+
+        let settings = Settings { tray_recent_count: 500, ..Settings::default() };
+        assert!(settings.validate().is_err());
+
+        let mut settings = Settings::default();
+        settings.secret_actions.insert("no_such_rule".to_string(), SecretAction::Mask);
+        assert_eq!(settings.validate().unwrap_err(), "Unknown secret rules: no_such_rule");
+
+        let mut settings = Settings::default();
+        settings.secret_actions.insert("high_entropy".to_string(), SecretAction::Expire(0));
+        assert!(settings.validate().is_err());
+        let hook = Hook {
+            name: "broken".to_string(),
+            pattern: Some("(".to_string()),
+            content_type: None,
+            command: "true".to_string(),
+            timeout_secs: 10,
+        };
+        let settings = Settings { hooks: vec![hook], ..Settings::default() };
+        assert!(settings.validate().is_err());
+    }
+}
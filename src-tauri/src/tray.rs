@@ -0,0 +1,98 @@
+use crate::db::{ContentType, EntrySummary};
+
+/// Menu item ids of history entries are this prefix followed by the entry id
+const ENTRY_ITEM_PREFIX: &str = "entry:";
+// Longer previews make the menu too wide
+const LABEL_LENGTH: usize = 40;
+
+pub fn entry_item_id(id: i64) -> String {
+    format!("{}{}", ENTRY_ITEM_PREFIX, id)
+}
+
+/// Entry id of a history menu item, None for the fixed items
+pub fn entry_id_from_item(item_id: &str) -> Option<i64> {
+    item_id.strip_prefix(ENTRY_ITEM_PREFIX)?.parse().ok()
+}
+
+/// One-line menu label for an entry
+pub fn entry_label(entry: &EntrySummary) -> String {
+    let label = match entry.content_type {
+        ContentType::Image => "[Image]".to_string(),
+        ContentType::Text => {
+            // Menus show a single line, so collapse newlines and runs of spaces
+            let text = entry.preview.as_deref().unwrap_or_default();
+            let collapsed = text.split_whitespace().collect::<Vec<_>>().join(" ");
+            if collapsed.chars().count() > LABEL_LENGTH || entry.truncated {
+                format!("{}…", collapsed.chars().take(LABEL_LENGTH).collect::<String>())
+            } else {
+                collapsed
+            }
+        }
+    };
+    if entry.pinned {
+        format!("📌 {}", label)
+    } else {
+        label
+    }
+}
+
+/// Entries listed in the tray menu: every pinned entry, then the most recent unpinned ones
+pub fn menu_entries(pinned: Vec<EntrySummary>, recent: Vec<EntrySummary>, recent_count: usize) -> Vec<EntrySummary> {
+    let recent = recent.into_iter().filter(|entry| !entry.pinned).take(recent_count);
+    pinned.into_iter().chain(recent).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn summary(id: i64, content_type: ContentType, preview: Option<&str>, pinned: bool) -> EntrySummary {
+        EntrySummary {
+            id,
+            content_type,
+            preview: preview.map(str::to_string),
+            truncated: false,
+            created_at: "2024-01-01 00:00:00 UTC".to_string(),
+            source_app_id: None,
+            source_app_name: None,
+            pinned,
+            thumbnail: None,
+        }
+    }
+
+    fn text_entry(id: i64, text: &str, pinned: bool) -> EntrySummary {
+        summary(id, ContentType::Text, Some(text), pinned)
+    }
+
+    #[test]
+    fn test_item_id_round_trip() {
+        assert_eq!(entry_id_from_item(&entry_item_id(42)), Some(42));
+        assert_eq!(entry_id_from_item("settings"), None);
+        assert_eq!(entry_id_from_item("entry:abc"), None);
+    }
+
+    #[test]
+    fn test_label_is_one_truncated_line() {
+        assert_eq!(entry_label(&text_entry(1, "  first\n\tsecond  ", false)), "first second");
+
+        let label = entry_label(&text_entry(1, &"a".repeat(100), false));
+        assert_eq!(label.chars().count(), LABEL_LENGTH + 1);
+        assert!(label.ends_with('…'));
+
+        assert_eq!(entry_label(&text_entry(1, "note", true)), "📌 note");
+        assert_eq!(entry_label(&summary(1, ContentType::Image, None, false)), "[Image]");
+
+        // A short preview of a longer text still shows that it continues
+        let truncated = EntrySummary { truncated: true, ..text_entry(1, "start", false) };
+        assert_eq!(entry_label(&truncated), "start…");
+    }
+
+    #[test]
+    fn test_menu_entries_lists_pinned_first_without_duplicates() {
+        let pinned = vec![text_entry(1, "pinned", true)];
+        let recent = vec![text_entry(3, "three", false), text_entry(1, "pinned", true), text_entry(2, "two", false)];
+
+        let ids: Vec<_> = menu_entries(pinned, recent, 1).into_iter().map(|e| e.id).collect();
+        assert_eq!(ids, vec![1, 3]);
+    }
+}
@@ -0,0 +1,413 @@
+use std::fmt;
+use std::io::Write;
+use std::process::{Command, Stdio};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use chrono::Utc;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use crate::capture::{Capture, CaptureEvent};
+use crate::crypto::hmac_sha256;
+use crate::db::{ClipboardEntry, ContentType};
+use crate::markers::CONCEALED_PLACEHOLDER;
+use crate::patterns::Patterns;
+
+pub const SIGNATURE_HEADER: &str = "X-ClipboardWatcher-Signature";
+pub const DELIVERY_HEADER: &str = "X-ClipboardWatcher-Delivery";
+const REQUEST_TIMEOUT_SECS: u64 = 10;
+// Deliveries attempted per pass over the queue
+const BATCH_SIZE: usize = 20;
+// Longest wait between queue checks, so retries run even without new entries
+const IDLE_INTERVAL: Duration = Duration::from_secs(60);
+const FIRST_RETRY_SECS: i64 = 5;
+const MAX_RETRY_SECS: i64 = 60 * 60;
+// Failed deliveries are dropped after this many attempts, about a day of retries
+const MAX_ATTEMPTS: u32 = 30;
+
+/// POSTs each new entry as JSON to a URL, off by default
+#[derive(Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WebhookSettings {
+    pub enabled: bool,
+    /// http:// or https:// endpoint
+    pub url: Option<String>,
+    /// Key for the HMAC-SHA256 signature header; None sends unsigned requests
+    pub secret: Option<String>,
+    /// Regex the copied text must contain; None sends every entry
+    pub pattern: Option<String>,
+    /// Only entries of this type; None sends text and images
+    pub content_type: Option<ContentType>,
+}
+
+// Settings are logged, the secret must not be
+impl fmt::Debug for WebhookSettings {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WebhookSettings")
+            .field("enabled", &self.enabled)
+            .field("url", &self.url)
+            .field("secret", &self.secret.as_ref().map(|_| "<redacted>"))
+            .field("pattern", &self.pattern)
+            .field("content_type", &self.content_type)
+            .finish()
+    }
+}
+
+impl WebhookSettings {
+    pub fn validate(&self) -> Result<(), String> {
+        match self.url.as_deref() {
+            // Anything else could also be read by curl as an option or a local file
+            Some(url) if !url.starts_with("http://") && !url.starts_with("https://") => {
+                return Err("Webhook URL must start with http:// or https://".to_string());
+            }
+            None if self.enabled => return Err("Set a URL before enabling the webhook".to_string()),
+            _ => {}
+        }
+        if let Some(pattern) = &self.pattern {
+            Regex::new(pattern).map_err(|e| format!("Invalid webhook pattern: {}", e))?;
+        }
+        Ok(())
+    }
+
+    /// Whether an entry is sent to the webhook
+    pub fn matches(&self, entry: &ClipboardEntry, patterns: &Patterns) -> bool {
+        if self.content_type.as_ref().is_some_and(|content_type| *content_type != entry.content_type) {
+            return false;
+        }
+        let Some(pattern) = &self.pattern else {
+            return true;
+        };
+        entry.text_content.as_deref().is_some_and(|text| patterns.is_match(pattern, text))
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// Value of the signature header for a request body
+pub fn signature(secret: &str, body: &str) -> String {
+    format!("sha256={}", hex(&hmac_sha256(secret.as_bytes(), body.as_bytes())))
+}
+
+/// Request body for an entry, shaped like the app's clipboard-changed event
+pub fn payload(entry: &ClipboardEntry) -> String {
+    json!({ "event": "clipboard-changed", "entry": entry }).to_string()
+}
+
+/// Seconds until a delivery that failed `attempts` times before is tried again
+pub fn retry_delay(attempts: u32) -> i64 {
+    FIRST_RETRY_SECS.saturating_mul(1 << attempts.min(20)).min(MAX_RETRY_SECS)
+}
+
+// Sends the request with curl, which ships with macOS and speaks https, returning the status code
+fn post(url: &str, body: &str, headers: &[(&str, String)]) -> Result<u16, String> {
+    let mut command = Command::new("curl");
+    // --disable must come first, it keeps ~/.curlrc out of the request
+    command
+        .args(["--disable", "--silent", "--show-error", "--proto", "=http,https"])
+        .args(["--max-time", &REQUEST_TIMEOUT_SECS.to_string()])
+        .args(["--output", "/dev/null", "--write-out", "%{http_code}"])
+        .args(["--header", "Content-Type: application/json", "--data-binary", "@-"]);
+    for (name, value) in headers {
+        command.arg("--header").arg(format!("{}: {}", name, value));
+    }
+    let mut child = command
+        .arg(url)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("Failed to run curl: {}", e))?;
+
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(body.as_bytes()).map_err(|e| e.to_string())?;
+    }
+    let output = child.wait_with_output().map_err(|e| e.to_string())?;
+    if !output.status.success() {
+        return Err(String::from_utf8_lossy(&output.stderr).trim().to_string());
+    }
+    String::from_utf8_lossy(&output.stdout).trim().parse().map_err(|_| "No HTTP status from curl".to_string())
+}
+
+enum Delivery {
+    Sent,
+    /// Not worth retrying, e.g. the entry was deleted or the endpoint refused it
+    Rejected(String),
+    Failed(String),
+}
+
+fn deliver(capture: &Capture, settings: &WebhookSettings, url: &str, entry_id: i64) -> Delivery {
+    let entry = match capture.db.read(|db| db.get_entry(entry_id)) {
+        Ok(Some(entry)) => entry,
+        Ok(None) => return Delivery::Rejected("the entry was deleted".to_string()),
+        // e.g. a locked database, which can be unlocked later
+        Err(e) => return Delivery::Failed(e),
+    };
+    let body = payload(&entry);
+    let mut headers = vec![(DELIVERY_HEADER, entry_id.to_string())];
+    if let Some(secret) = settings.secret.as_deref() {
+        headers.push((SIGNATURE_HEADER, signature(secret, &body)));
+    }
+    match post(url, &body, &headers) {
+        Ok(200..=299) => Delivery::Sent,
+        Ok(status @ (408 | 429 | 500..)) => Delivery::Failed(format!("HTTP {}", status)),
+        Ok(status) => Delivery::Rejected(format!("HTTP {}", status)),
+        Err(e) => Delivery::Failed(e),
+    }
+}
+
+/// Sends the queued deliveries that are due, returning how long until the next one is
+pub fn deliver_due(capture: &Capture) -> Result<Duration, String> {
+    let settings = capture.settings.read().unwrap().webhook.clone();
+    // The queue is kept while the webhook is off and sent once it's back on
+    let Some(url) = settings.url.as_deref().filter(|_| settings.enabled) else {
+        return Ok(IDLE_INTERVAL);
+    };
+
+    let now = Utc::now().timestamp();
+    for queued in capture.db.read(|db| db.due_webhooks(now, BATCH_SIZE))? {
+        let entry_id = queued.entry_id;
+        match deliver(capture, &settings, url, entry_id) {
+            Delivery::Sent => {
+                println!("[WEBHOOK] Delivered entry {}", entry_id);
+                capture.db.write(move |db| db.remove_webhook(entry_id))?;
+            }
+            Delivery::Rejected(reason) => {
+                println!("[WEBHOOK] Dropping entry {}: {}", entry_id, reason);
+                capture.db.write(move |db| db.remove_webhook(entry_id))?;
+            }
+            Delivery::Failed(error) if queued.attempts + 1 >= MAX_ATTEMPTS => {
+                println!("[WEBHOOK] Dropping entry {} after {} failed attempts: {}", entry_id, MAX_ATTEMPTS, error);
+                capture.db.write(move |db| db.remove_webhook(entry_id))?;
+            }
+            Delivery::Failed(error) => {
+                let delay = retry_delay(queued.attempts);
+                println!("[WEBHOOK] Delivery of entry {} failed, retrying in {} s: {}", entry_id, delay, error);
+                capture.db.write(move |db| db.retry_webhook(entry_id, now + delay, &error))?;
+            }
+        }
+    }
+
+    let wait = match capture.db.read(|db| db.next_webhook_attempt())? {
+        Some(next) => Duration::from_secs((next - Utc::now().timestamp()).max(0) as u64),
+        None => IDLE_INTERVAL,
+    };
+    Ok(wait.min(IDLE_INTERVAL))
+}
+
+// Secrets, whether masked or left to expire, and concealed copies never leave the machine
+fn is_sensitive(entry: &ClipboardEntry) -> bool {
+    entry.flagged || entry.expires_at.is_some() || entry.text_content.as_deref() == Some(CONCEALED_PLACEHOLDER)
+}
+
+// Adds a new entry to the queue if the webhook wants it
+fn queue_entry(capture: &Capture, entry: &ClipboardEntry) -> bool {
+    if is_sensitive(entry) {
+        return false;
+    }
+    let settings = capture.settings.read().unwrap().webhook.clone();
+    let patterns = capture.patterns.read().unwrap();
+    let Some(entry_id) = entry.id.filter(|_| settings.enabled && settings.matches(entry, &patterns)) else {
+        return false;
+    };
+    let now = Utc::now().timestamp();
+    match capture.db.write(move |db| db.queue_webhook(entry_id, now)) {
+        Ok(()) => true,
+        Err(e) => {
+            println!("[WEBHOOK] Failed to queue entry {}: {}", entry_id, e);
+            false
+        }
+    }
+}
+
+/// Queues entries this process captures and delivers them on a worker thread,
+/// retrying failures with backoff
+pub fn attach(capture: Arc<Capture>) {
+    let (wake, woken) = mpsc::channel::<()>();
+
+    let worker = Arc::clone(&capture);
+    thread::spawn(move || loop {
+        // Only the capturing process delivers, a follower would send everything twice
+        let wait = if worker.is_capturing() {
+            deliver_due(&worker).unwrap_or_else(|e| {
+                println!("[WEBHOOK] Failed to process the queue: {}", e);
+                IDLE_INTERVAL
+            })
+        } else {
+            IDLE_INTERVAL
+        };
+        if let Err(RecvTimeoutError::Disconnected) = woken.recv_timeout(wait) {
+            break;
+        }
+    });
+
+    let subscribed = Arc::clone(&capture);
+    capture.subscribe(move |event| match event {
+        CaptureEvent::EntryAdded(entry) if subscribed.is_capturing() && queue_entry(&subscribed, entry) => {
+            let _ = wake.send(());
+        }
+        CaptureEvent::CaptureStarted | CaptureEvent::SettingsChanged(_) => {
+            let _ = wake.send(());
+        }
+        _ => {}
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::capture::TestCapture;
+    use std::io::{BufRead, BufReader, Read};
+    use std::net::TcpListener;
+
+    // Local endpoint answering one request per status, handing back each request's headers and body
+    fn stand_in(statuses: Vec<u16>) -> (String, mpsc::Receiver<(Vec<String>, String)>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || {
+            for status in statuses {
+                let (stream, _) = listener.accept().unwrap();
+                let mut reader = BufReader::new(stream);
+                let mut headers = Vec::new();
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    if line.trim().is_empty() {
+                        break;
+                    }
+                    headers.push(line.trim().to_string());
+                }
+                let length = headers
+                    .iter()
+                    .find_map(|header| header.to_lowercase().strip_prefix("content-length:").map(|value| value.trim().parse().unwrap()))
+                    .unwrap_or(0);
+                let mut body = vec![0u8; length];
+                reader.read_exact(&mut body).unwrap();
+                let response = format!("HTTP/1.1 {} Stand-in\r\nContent-Length: 0\r\nConnection: close\r\n\r\n", status);
+                reader.get_mut().write_all(response.as_bytes()).unwrap();
+                sender.send((headers, String::from_utf8(body).unwrap())).unwrap();
+            }
+        });
+        (url, receiver)
+    }
+
+    fn settings(url: &str) -> WebhookSettings {
+        WebhookSettings { enabled: true, url: Some(url.to_string()), ..WebhookSettings::default() }
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(WebhookSettings::default().validate().is_ok());
+        assert!(settings("https://example.com/hook").validate().is_ok());
+        assert!(settings("file:///etc/passwd").validate().is_err());
+        assert!(settings("-o/tmp/x").validate().is_err());
+        assert!(WebhookSettings { enabled: true, ..WebhookSettings::default() }.validate().is_err());
+        assert!(WebhookSettings { pattern: Some("(".to_string()), ..WebhookSettings::default() }.validate().is_err());
+    }
+
+    #[test]
+    fn test_matches() {
+        let text = ClipboardEntry::new_text_entry("snippet: let x = 1;".to_string());
+        let image = ClipboardEntry::new_image_entry("data:image/png;base64,AAAA".to_string());
+
+        let none = Patterns::default();
+        assert!(WebhookSettings::default().matches(&text, &none));
+        assert!(WebhookSettings::default().matches(&image, &none));
+
+        let snippets = WebhookSettings { pattern: Some("^snippet:".to_string()), ..WebhookSettings::default() };
+        let patterns = Patterns::compile(snippets.pattern.as_deref()).unwrap();
+        assert!(snippets.matches(&text, &patterns));
+        assert!(!snippets.matches(&ClipboardEntry::new_text_entry("hello".to_string()), &patterns));
+        assert!(!snippets.matches(&image, &patterns));
+        assert!(!snippets.matches(&text, &none));
+
+        let images = WebhookSettings { content_type: Some(ContentType::Image), ..WebhookSettings::default() };
+        assert!(images.matches(&image, &none));
+        assert!(!images.matches(&text, &none));
+    }
+
+    #[test]
+    fn test_retry_delay() {
+        assert_eq!(retry_delay(0), 5);
+        assert_eq!(retry_delay(1), 10);
+        assert_eq!(retry_delay(3), 40);
+        assert_eq!(retry_delay(100), MAX_RETRY_SECS);
+    }
+
+    #[test]
+    fn test_delivers_signed_entries_with_retries() {
+        let capture = TestCapture::new("webhook_deliver");
+        let (url, requests) = stand_in(vec![503, 204]);
+        capture.settings.write().unwrap().webhook = WebhookSettings { secret: Some("s3cret".to_string()), ..settings(&url) };
+        let id = capture.db.save_entry(ClipboardEntry::new_text_entry("hello".to_string())).unwrap();
+        capture.db.write(move |db| db.queue_webhook(id, 0)).unwrap();
+
+        // The endpoint is down, the entry stays queued for later
+        let wait = deliver_due(&capture).unwrap();
+        assert!(wait > Duration::ZERO && wait <= Duration::from_secs(5));
+        requests.recv().unwrap();
+        let queued = capture.db.read(|db| db.due_webhooks(i64::MAX, 10)).unwrap();
+        assert_eq!(queued[0].attempts, 1);
+
+        capture.db.write(move |db| db.retry_webhook(id, 0, "")).unwrap();
+        deliver_due(&capture).unwrap();
+        let (headers, body) = requests.recv().unwrap();
+        assert!(capture.db.read(|db| db.next_webhook_attempt()).unwrap().is_none());
+
+        let sent: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(sent["entry"]["text_content"], "hello");
+        assert!(headers.contains(&format!("{}: {}", SIGNATURE_HEADER, signature("s3cret", &body))));
+        assert!(headers.contains(&format!("{}: {}", DELIVERY_HEADER, id)));
+    }
+
+    #[test]
+    fn test_sensitive_entries_are_not_queued() {
+        let capture = TestCapture::new("webhook_sensitive");
+        capture.settings.write().unwrap().webhook = settings("https://example.com/hook");
+        let queue = |mut entry: ClipboardEntry| {
+            entry.id = Some(capture.db.save_entry(entry.clone()).unwrap());
+            queue_entry(&capture, &entry)
+        };
+
+        assert!(queue(ClipboardEntry::new_text_entry("hello".to_string())));
+        assert!(!queue(ClipboardEntry { flagged: true, ..ClipboardEntry::new_text_entry("AKIA********".to_string()) }));
+        assert!(!queue(ClipboardEntry { expires_at: Some(0), ..ClipboardEntry::new_text_entry("otp".to_string()) }));
+        assert!(!queue(ClipboardEntry::new_text_entry(CONCEALED_PLACEHOLDER.to_string())));
+    }
+
+    #[test]
+    fn test_gives_up_after_max_attempts() {
+        let capture = TestCapture::new("webhook_give_up");
+        let (url, requests) = stand_in(vec![503]);
+        capture.settings.write().unwrap().webhook = settings(&url);
+        let id = capture.db.save_entry(ClipboardEntry::new_text_entry("hello".to_string())).unwrap();
+        capture.db.write(move |db| db.queue_webhook(id, 0)).unwrap();
+        for _ in 1..MAX_ATTEMPTS {
+            capture.db.write(move |db| db.retry_webhook(id, 0, "HTTP 503")).unwrap();
+        }
+
+        deliver_due(&capture).unwrap();
+        requests.recv().unwrap();
+        assert!(capture.db.read(|db| db.next_webhook_attempt()).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_drops_deleted_and_refused_entries() {
+        let capture = TestCapture::new("webhook_drop");
+        let (url, requests) = stand_in(vec![400]);
+        capture.settings.write().unwrap().webhook = settings(&url);
+        let refused = capture.db.save_entry(ClipboardEntry::new_text_entry("refused".to_string())).unwrap();
+        capture.db.write(move |db| db.queue_webhook(refused, 0)).unwrap();
+        capture.db.write(|db| db.queue_webhook(9999, 0)).unwrap();
+
+        deliver_due(&capture).unwrap();
+        requests.recv().unwrap();
+        assert!(capture.db.read(|db| db.next_webhook_attempt()).unwrap().is_none());
+    }
+}
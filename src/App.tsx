@@ -1,33 +1,115 @@
-import { useState, useEffect, useMemo } from "react";
+import { useState, useEffect, useRef, useCallback, UIEvent } from "react";
 import { invoke } from "@tauri-apps/api/core";
 import { listen } from "@tauri-apps/api/event";
 import { X, Trash2, Search } from "lucide-react";
-import { ClipboardEntry, ClipboardEntryData } from "./types";
+import { ClipboardEntry, ClipboardEntryData, EntryCounts, EntryCursor, EntryFilter, EntryPage, EntrySummary, SearchHit } from "./types";
 import Sidebar from "./components/Sidebar";
 import ClipboardCard from "./components/ClipboardCard";
 import { Button } from "./components/ui/button";
 import { Input } from "./components/ui/input";
 
+type Category = "all" | "text" | "images";
+
+// Entries fetched per page, and search results shown at most
+const PAGE_SIZE = 50;
+const SEARCH_LIMIT = 100;
+// Distance from the bottom, in pixels, at which the next page is loaded
+const LOAD_MORE_THRESHOLD = 400;
+
+function categoryFilter(category: Category): EntryFilter {
+  const contentType = category === "text" ? "Text" : category === "images" ? "Image" : null;
+  return { content_type: contentType, source_app: null };
+}
+
 function App() {
   const [clipboardEvents, setClipboardEvents] = useState<ClipboardEntry[]>([]);
-  const [activeCategory, setActiveCategory] = useState<"all" | "text" | "images">("all");
+  const [nextCursor, setNextCursor] = useState<EntryCursor | null>(null);
+  const [counts, setCounts] = useState<EntryCounts>({ text: 0, image: 0 });
+  const [activeCategory, setActiveCategory] = useState<Category>("all");
   const [searchQuery, setSearchQuery] = useState("");
+  const [searchResults, setSearchResults] = useState<ClipboardEntry[] | null>(null);
+  // Read by the event listeners, which are registered once
+  const activeCategoryRef = useRef<Category>("all");
+  const loadingRef = useRef(false);
+
+  const filteredEvents = searchQuery && searchResults && activeCategory !== "images" ? searchResults : clipboardEvents;
+
+  const refreshCounts = useCallback(() => {
+    invoke<EntryCounts>("get_entry_counts")
+      .then(setCounts)
+      .catch((error) => {
+        console.error("[ERROR] Failed to count entries:", error);
+      });
+  }, []);
+
+  // Loads the page after the cursor, or the first page when it is null
+  const loadPage = useCallback((category: Category, cursor: EntryCursor | null) => {
+    loadingRef.current = true;
+    invoke<EntryPage>("list_entries", { cursor, limit: PAGE_SIZE, filters: categoryFilter(category) })
+      .then((page) => {
+        // A page of a category that is no longer shown
+        if (category !== activeCategoryRef.current) {
+          return;
+        }
+        const entries = page.entries.map(ClipboardEntry.fromSummary);
+        setClipboardEvents((prev) => {
+          if (!cursor) {
+            return entries;
+          }
+          const loaded = new Set(prev.map((e) => e.id));
+          return [...prev, ...entries.filter((e) => !loaded.has(e.id))];
+        });
+        setNextCursor(page.next_cursor);
+        console.log("[LOAD] Loaded page of", entries.length, "entries");
+      })
+      .catch((error) => {
+        console.error("[ERROR] Failed to load entries:", error);
+      })
+      .finally(() => {
+        loadingRef.current = false;
+      });
+  }, []);
+
+  const handleCategoryChange = (category: Category) => {
+    activeCategoryRef.current = category;
+    setActiveCategory(category);
+    setNextCursor(null);
+    loadPage(category, null);
+  };
 
-  const textCount = useMemo(() => clipboardEvents.filter((e) => e.isText()).length, [clipboardEvents]);
-  const imageCount = useMemo(() => clipboardEvents.filter((e) => e.isImage()).length, [clipboardEvents]);
-
-  const filteredEvents = useMemo(() => {
-    let filtered: ClipboardEntry[];
-    if (activeCategory === "text") {
-      filtered = clipboardEvents.filter((e) => e.isText());
-    } else if (activeCategory === "images") {
-      filtered = clipboardEvents.filter((e) => e.isImage());
-    } else {
-      filtered = clipboardEvents;
+  const handleScroll = (event: UIEvent<HTMLDivElement>) => {
+    const { scrollTop, scrollHeight, clientHeight } = event.currentTarget;
+    if (nextCursor && !loadingRef.current && !searchQuery && scrollHeight - scrollTop - clientHeight < LOAD_MORE_THRESHOLD) {
+      loadPage(activeCategory, nextCursor);
+    }
+  };
+
+  useEffect(() => {
+    if (!searchQuery) {
+      setSearchResults(null);
+      return;
     }
 
-    return filtered;
-  }, [clipboardEvents, activeCategory]);
+    let cancelled = false;
+    invoke<SearchHit[] | null>("search_clipboard_entries", { query: searchQuery })
+      .then(async (hits) => {
+        // null means a newer query superseded this one
+        if (!hits || cancelled) {
+          return;
+        }
+        const ids = hits.slice(0, SEARCH_LIMIT).map((hit) => hit.id);
+        const summaries = await invoke<EntrySummary[]>("get_entry_summaries", { ids });
+        if (!cancelled) {
+          setSearchResults(summaries.map(ClipboardEntry.fromSummary));
+        }
+      })
+      .catch((error) => {
+        console.error("[ERROR] Failed to search:", error);
+      });
+    return () => {
+      cancelled = true;
+    };
+  }, [searchQuery]);
 
   useEffect(() => {
     console.log("[INIT] React App mounting at:", new Date().toISOString());
@@ -46,6 +128,11 @@ function App() {
     listen<ClipboardEntryData>("clipboard-changed", (event) => {
       console.log(`[EVENT-${listenerInstanceId}] clipboard-changed received:`, event.payload);
       const entry = new ClipboardEntry(event.payload);
+      refreshCounts();
+      const category = activeCategoryRef.current;
+      if ((category === "text" && !entry.isText()) || (category === "images" && !entry.isImage())) {
+        return;
+      }
       setClipboardEvents((prev) => {
         const isDuplicate = prev.some((e) => e.id === entry.id);
         if (isDuplicate) {
@@ -63,21 +150,15 @@ function App() {
     listen<number>("clipboard-deleted", (event) => {
       console.log("[EVENT] clipboard-deleted received:", event.payload);
       setClipboardEvents((prev) => prev.filter((e) => e.id !== event.payload));
+      setSearchResults((prev) => prev && prev.filter((e) => e.id !== event.payload));
+      refreshCounts();
     }).then((fn) => {
       unlistenClipboardDeleted = fn;
       console.log("[MOUNT] ✓ clipboard-deleted listener registered");
     });
 
-    invoke<ClipboardEntryData[]>("load_clipboard_events_at_startup")
-      .then((entries) => {
-        console.log("[MOUNT] Loaded entries:", entries);
-        const clipboardEntries = entries.map((e) => new ClipboardEntry(e));
-        setClipboardEvents(clipboardEntries);
-        console.log("[MOUNT] ✓ Initial data loaded:", clipboardEntries.length);
-      })
-      .catch((error) => {
-        console.error("[ERROR] Failed to load initial data:", error);
-      });
+    loadPage("all", null);
+    refreshCounts();
 
     return () => {
       if (unlistenClipboardChanged) {
@@ -89,7 +170,7 @@ function App() {
         console.log("[CLEANUP] clipboard-deleted listener removed");
       }
     };
-  }, []);
+  }, [loadPage, refreshCounts]);
 
   const handleDelete = async (item: ClipboardEntry) => {
     console.log("[DELETE] Deleting item:", item);
@@ -98,13 +179,10 @@ function App() {
   };
 
   const handlePaste = async (item: ClipboardEntry) => {
+    // Listed entries only hold a preview, so the backend copies the full content
     try {
-      if (item.isText()) {
-        await navigator.clipboard.writeText(item.text);
-        console.log("[PASTE] Pasted text:", item.text);
-      } else if (item.isImage()) {
-        console.log("[PASTE] Image paste not yet implemented");
-      }
+      await invoke("copy_clipboard_entry", { id: item.id });
+      console.log("[PASTE] Copied entry:", item.id);
     } catch (error) {
       console.error("[ERROR] Failed to paste:", error);
     }
@@ -115,6 +193,8 @@ function App() {
     try {
       await invoke("clear_clipboard_history");
       setClipboardEvents([]);
+      setNextCursor(null);
+      refreshCounts();
     } catch (error) {
       console.error("[ERROR] Failed to clear:", error);
     }
@@ -131,10 +211,10 @@ function App() {
   return (
     <div className="flex h-screen overflow-hidden bg-background">
       <Sidebar
-        totalCount={clipboardEvents.length}
-        textCount={textCount}
-        imageCount={imageCount}
-        onCategoryChange={setActiveCategory}
+        totalCount={counts.text + counts.image}
+        textCount={counts.text}
+        imageCount={counts.image}
+        onCategoryChange={handleCategoryChange}
         onToggle={() => {}}
       />
 
@@ -156,13 +236,13 @@ function App() {
         </header>
 
         {/* Search Bar */}
-        {activeCategory === "text" && (
+        {activeCategory !== "images" && (
           <div className="border-b px-4 py-3">
             <div className="relative">
               <Search className="absolute left-3 top-1/2 h-4 w-4 -translate-y-1/2 text-muted-foreground" />
               <Input
                 type="text"
-                placeholder="Search text items..."
+                placeholder={activeCategory === "all" ? "Search clipboard history..." : "Search text items..."}
                 value={searchQuery}
                 onChange={(e) => setSearchQuery(e.target.value)}
                 className="pl-9 pr-9"
@@ -183,7 +263,7 @@ function App() {
         )}
 
         {/* Content */}
-        <div className="flex-1 overflow-y-auto p-4">
+        <div className="flex-1 overflow-y-auto p-4" onScroll={handleScroll}>
           {filteredEvents.length === 0 ? (
             <div className="flex h-full flex-col items-center justify-center text-center">
               <p className="text-base font-medium text-foreground">
@@ -6,6 +6,117 @@ export interface ClipboardEntryData {
   text_content: string | null;
   image_path: string | null;
   created_at: string;
+  expires_at: number | null;
+  source_app_id: string | null;
+  source_app_name: string | null;
+  source_window_title: string | null;
+  pinned: boolean;
+}
+
+export interface EntrySummary {
+  id: number;
+  content_type: ContentType;
+  preview: string | null;
+  truncated: boolean;
+  created_at: string;
+  source_app_id: string | null;
+  source_app_name: string | null;
+  pinned: boolean;
+  /** Path of an image entry's PNG, served under ENTRY_URL */
+  thumbnail: string | null;
+}
+
+/** Base URL of the app's `entry` URI scheme */
+export const ENTRY_URL = "entry://localhost/";
+
+export interface EntryCounts {
+  text: number;
+  image: number;
+}
+
+export interface EntryFilter {
+  content_type: ContentType | null;
+  source_app: string | null;
+}
+
+export interface EntryCursor {
+  created_at: string;
+  id: number;
+}
+
+export interface EntryPage {
+  entries: EntrySummary[];
+  next_cursor: EntryCursor | null;
+}
+
+export interface SearchHit {
+  id: number;
+  score: number;
+}
+
+export type SecretAction = "skip" | "mask" | { expire: number };
+
+export interface AppFilter {
+  mode: "deny" | "allow";
+  apps: string[];
+}
+
+export interface Settings {
+  poll_interval_ms: number;
+  max_entries: number | null;
+  max_age_days: number | null;
+  max_entry_kb: number | null;
+  app_filter: AppFilter;
+  concealed_policy: "skip" | "placeholder";
+  secret_actions: Record<string, SecretAction>;
+  hotkey: string | null;
+  window_position: "center" | "tray_icon" | "cursor";
+  tray_recent_count: number;
+  http_api: HttpApiSettings;
+  hooks: Hook[];
+  webhook: WebhookSettings;
+  rewrite_rules: RewriteRule[];
+}
+
+export interface HttpApiSettings {
+  enabled: boolean;
+  port: number;
+  token: string | null;
+}
+
+export interface Hook {
+  name: string;
+  pattern: string | null;
+  content_type: ContentType | null;
+  command: string;
+  timeout_secs: number;
+}
+
+export interface WebhookSettings {
+  enabled: boolean;
+  url: string | null;
+  secret: string | null;
+  pattern: string | null;
+  content_type: ContentType | null;
+}
+
+export type RewriteAction =
+  | { replace: { find: string; with: string } }
+  | "strip_trailing_newline"
+  | "remove_tracking_params"
+  | "straight_quotes";
+
+export interface RewriteRule {
+  name: string;
+  pattern: string | null;
+  app: string | null;
+  action: RewriteAction;
+}
+
+export interface HotkeyStatus {
+  hotkey: string | null;
+  registered: boolean;
+  error: string | null;
 }
 
 export class ClipboardEntry {
@@ -23,6 +134,22 @@ export class ClipboardEntry {
     this.created_at = data.created_at;
   }
 
+  /** Entry shown from a summary: the text is the preview and the image is loaded by URL */
+  static fromSummary(summary: EntrySummary): ClipboardEntry {
+    return new ClipboardEntry({
+      id: summary.id,
+      content_type: summary.content_type,
+      text_content: summary.preview && summary.truncated ? `${summary.preview}…` : summary.preview,
+      image_path: summary.thumbnail ? ENTRY_URL + summary.thumbnail : null,
+      created_at: summary.created_at,
+      expires_at: null,
+      source_app_id: summary.source_app_id,
+      source_app_name: summary.source_app_name,
+      source_window_title: null,
+      pinned: summary.pinned,
+    });
+  }
+
   get type(): string {
     return this.content_type;
   }